@@ -0,0 +1,187 @@
+use crate::cli::{BatchOpts, Opts};
+use crate::precheck::assert_exists;
+use minijudge_rust::error::{Error, Result};
+use minijudge_rust::judge::{self, JudgeOutput};
+/// This module contains the batch judging of many submissions against one problem, sharing the
+/// compiled checker and the sandbox pool between the submissions.
+use minijudge_rust::languages::LanguageRegistry;
+use minijudge_rust::problem::Problem;
+use minijudge_rust::{Judge, Submission};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+const SUMMARY_FILENAME: &str = "summary.json";
+
+/// A submission listed in the manifest.
+#[derive(Deserialize)]
+pub struct ManifestEntry {
+    /// The ID of the submission, used as the name of its verdict file.
+    pub id: String,
+    /// The path to the source code, relative to the manifest.
+    pub source: String,
+    /// The language of the submission. If not provided, the language is inferred from the
+    /// extension of the source file.
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// The verdict of a submission in the summary.
+#[derive(Serialize)]
+pub struct SummaryEntry {
+    pub id: String,
+    pub language: String,
+    pub verdict: String,
+    pub time: f64,
+    pub memory: i64,
+}
+
+/// Read the manifest, resolving the sources relative to the manifest and the languages of the
+/// submissions. The whole manifest is checked before any submission is judged.
+pub fn read_manifest(manifest_path: &str, languages: &LanguageRegistry) -> Result<Vec<Submission>> {
+    let manifest_file = std::fs::File::open(manifest_path)?;
+    let entries: Vec<ManifestEntry> = serde_yaml::from_reader(manifest_file)?;
+    let base = Path::new(manifest_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+
+    let mut ids = HashSet::new();
+    let mut submissions = vec![];
+    for entry in entries {
+        if entry.id.is_empty() || entry.id.contains(&['/', '\\'][..]) {
+            return Err(Error::Option(format!(
+                "The submission ID {:?} is invalid. It must be non-empty and not contain slashes.",
+                entry.id
+            )));
+        }
+        if !ids.insert(entry.id.clone()) {
+            return Err(Error::Option(format!(
+                "The submission ID {} is duplicated.",
+                entry.id
+            )));
+        }
+
+        let source = base.join(&entry.source).to_string_lossy().to_string();
+        assert_exists(&source, &format!("source file of submission {}", entry.id))?;
+        let language = match entry.language {
+            Some(language) if languages.get(&language).is_some() => language,
+            Some(language) => {
+                return Err(Error::Option(format!(
+                    "The language {} of submission {} is not defined.",
+                    language, entry.id
+                )))
+            }
+            None => languages.infer(&source)?.code.clone(),
+        };
+
+        submissions.push(Submission {
+            id: Some(entry.id),
+            source,
+            language,
+        });
+    }
+
+    Ok(submissions)
+}
+
+/// Write the verdict of a submission to the output folder. This is the verdict sink of the judge,
+/// so that the verdict is written as soon as the submission is judged, before the progress event
+/// announcing it.
+pub fn write_verdict(
+    opts: &Opts,
+    batch_opts: &BatchOpts,
+    problem: &Problem,
+    submission: &Submission,
+    judge_output: &JudgeOutput,
+) -> Result<()> {
+    let verdict_file = Path::new(&batch_opts.output_dir).join(format!(
+        "{}.{}",
+        submission.id.as_deref().unwrap_or_default(),
+        opts.verdict_format_for(None).extension()
+    ));
+    std::fs::write(
+        verdict_file,
+        opts.verdict_format_for(None)
+            .render(judge_output, Some(problem), false)?,
+    )?;
+
+    Ok(())
+}
+
+/// Judge the submissions one by one, with the verdicts written by `write_verdict`, and write a
+/// summary of all submissions at the end. Returns whether no submission gets a system error.
+pub fn batch(
+    opts: &Opts,
+    batch_opts: &BatchOpts,
+    judge: &Judge,
+    submissions: &[Submission],
+) -> Result<bool> {
+    let output_dir = Path::new(&batch_opts.output_dir);
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut summary = vec![];
+    for (i, submission) in submissions.iter().enumerate() {
+        let id = submission.id.clone().unwrap_or_default();
+        log::info!(
+            "Judging submission {} ({}/{}).",
+            id,
+            i + 1,
+            submissions.len()
+        );
+        let judge_output = judge.judge(submission)?;
+
+        summary.push(SummaryEntry {
+            id,
+            language: submission.language.clone(),
+            verdict: judge_output.verdict,
+            time: judge_output.time,
+            memory: judge_output.memory,
+        });
+    }
+
+    std::fs::write(
+        output_dir.join(SUMMARY_FILENAME),
+        serde_json::to_string_pretty(&summary)?,
+    )?;
+    // The summary table is not mixed into the progress written to standard output.
+    if opts.progress_to_stdout() {
+        print_summary(&mut std::io::stderr(), &summary)?;
+    } else {
+        print_summary(&mut std::io::stdout(), &summary)?;
+    }
+
+    Ok(summary.iter().all(|s| s.verdict != judge::VERDICT_SE))
+}
+
+fn print_summary(out: &mut dyn Write, summary: &[SummaryEntry]) -> Result<()> {
+    let id_width = summary
+        .iter()
+        .map(|s| s.id.len())
+        .fold("Submission".len(), usize::max);
+    writeln!(
+        out,
+        "{:<width$} {:<8} {:<8} {:>8} {:>10}",
+        "Submission",
+        "Language",
+        "Verdict",
+        "Time",
+        "Memory",
+        width = id_width
+    )?;
+
+    for entry in summary {
+        writeln!(
+            out,
+            "{:<width$} {:<8} {:<8} {:>8.3} {:>10}",
+            entry.id,
+            entry.language,
+            entry.verdict,
+            entry.time,
+            entry.memory,
+            width = id_width
+        )?;
+    }
+
+    Ok(())
+}
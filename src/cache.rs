@@ -0,0 +1,176 @@
+use crate::error::Result;
+use crate::judge::CompileOutput;
+use crate::sandbox::remove_if_exists;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+/// This module contains the cache of compiled submissions, so that rejudging a submission does
+/// not compile it again.
+use std::path::{Path, PathBuf};
+
+const ENTRY_FILENAME: &str = "entry.json";
+const ARTIFACTS_FOLDER: &str = "artifacts";
+
+/// The result of compiling a submission, as kept in the cache.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheEntry {
+    /// The verdict of the submission failing to compile, or `None` if it compiles.
+    pub verdict: Option<String>,
+    pub compile: CompileOutput,
+    /// The files produced by the compilation, relative to the box, e.g. the class files of the
+    /// nested classes in Java besides the executable.
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+}
+
+/// A cache of compiled submissions in a folder, shared by judge processes. Each entry is a folder
+/// named after its key, holding the entry and a copy of the compiled artifacts if the submission
+/// compiles.
+#[derive(Debug, Clone)]
+pub struct CompileCache {
+    path: PathBuf,
+}
+
+impl CompileCache {
+    pub fn open(path: &str) -> Result<CompileCache> {
+        std::fs::create_dir_all(path)?;
+
+        Ok(CompileCache {
+            path: PathBuf::from(path),
+        })
+    }
+
+    /// Compute the key of an entry from everything affecting the compilation, e.g. the source
+    /// code, the definition of the language and the version of the compiler.
+    pub fn key(parts: &[&[u8]]) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            // The length is hashed as well, so that the parts cannot run into each other.
+            hasher.update((part.len() as u64).to_le_bytes());
+            hasher.update(part);
+        }
+
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Find the entry with the key. The artifacts are copied into the destination folder if the
+    /// submission compiles. Unreadable entries are treated as missing.
+    pub fn get(&self, key: &str, destination: &Path) -> Option<CacheEntry> {
+        let folder = self.path.join(key);
+        let entry = std::fs::read_to_string(folder.join(ENTRY_FILENAME)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&entry).ok()?;
+
+        if entry.verdict.is_none() {
+            // Entries stored before the artifacts were kept hold no artifacts.
+            if entry.artifacts.is_empty() {
+                return None;
+            }
+            copy_files(
+                &folder.join(ARTIFACTS_FOLDER),
+                destination,
+                &entry.artifacts,
+            )
+            .ok()?;
+        }
+
+        Some(entry)
+    }
+
+    /// Store the entry with the key, together with the artifacts in the source folder if the
+    /// submission compiles. The entry is written to a temporary folder first, so that other
+    /// processes never read a partial entry.
+    pub fn put(&self, key: &str, entry: &CacheEntry, source: &Path) -> Result<()> {
+        let folder = self.path.join(key);
+        if folder.exists() {
+            return Ok(());
+        }
+
+        let temporary = self
+            .path
+            .join(format!("{}.tmp-{}", key, std::process::id()));
+        std::fs::create_dir_all(&temporary)?;
+        if entry.verdict.is_none() {
+            copy_files(source, &temporary.join(ARTIFACTS_FOLDER), &entry.artifacts)?;
+        }
+        std::fs::write(
+            temporary.join(ENTRY_FILENAME),
+            serde_json::to_string(entry)?,
+        )?;
+
+        if std::fs::rename(&temporary, &folder).is_err() {
+            // Another process stored the same entry first.
+            std::fs::remove_dir_all(&temporary)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Copy the files, given by their paths relative to the source folder, to the same paths in the
+/// destination folder. Existing files in the destination are replaced instead of written through,
+/// as they may be links left by a program.
+fn copy_files(source: &Path, destination: &Path, files: &[String]) -> Result<()> {
+    for file in files {
+        let destination_path = destination.join(file);
+        if let Some(parent) = destination_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        remove_if_exists(&destination_path)?;
+        std::fs::copy(source.join(file), destination_path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::judge::{Excerpt, Meta};
+
+    #[test]
+    fn test_key() {
+        let key = CompileCache::key(&[b"ab", b"c"]);
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, CompileCache::key(&[b"ab", b"c"]));
+        assert_ne!(key, CompileCache::key(&[b"a", b"bc"]));
+    }
+
+    #[test]
+    fn test_multiple_artifacts() {
+        let folder = std::env::temp_dir().join(format!("minijudge-cache-{}", std::process::id()));
+        let (source, destination) = (folder.join("source"), folder.join("destination"));
+        std::fs::create_dir_all(source.join("pkg")).unwrap();
+        std::fs::write(source.join("Main.class"), "main").unwrap();
+        std::fs::write(source.join("pkg/Main$Reader.class"), "reader").unwrap();
+
+        let cache = CompileCache::open(&folder.join("cache").to_string_lossy()).unwrap();
+        let empty = Excerpt {
+            head: String::new(),
+            tail: String::new(),
+            size: 0,
+        };
+        let entry = CacheEntry {
+            verdict: None,
+            compile: CompileOutput::new(&Meta::default(), empty.clone(), empty),
+            artifacts: vec![
+                "Main.class".to_string(),
+                "pkg/Main$Reader.class".to_string(),
+            ],
+        };
+        cache.put("key", &entry, &source).unwrap();
+        assert!(cache.get("missing", &destination).is_none());
+        // A link at the path of an artifact is replaced instead of written through.
+        std::fs::create_dir_all(&destination).unwrap();
+        std::fs::write(folder.join("host.txt"), "host").unwrap();
+        std::os::unix::fs::symlink(folder.join("host.txt"), destination.join("Main.class"))
+            .unwrap();
+        let cached = cache.get("key", &destination).unwrap();
+        let main = std::fs::read_to_string(destination.join("Main.class")).unwrap();
+        let reader = std::fs::read_to_string(destination.join("pkg/Main$Reader.class")).unwrap();
+        let host = std::fs::read_to_string(folder.join("host.txt")).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(cached.artifacts, entry.artifacts);
+        assert_eq!((main.as_str(), reader.as_str()), ("main", "reader"));
+        assert_eq!(host, "host");
+    }
+}
@@ -0,0 +1,210 @@
+use crate::cli::{CalibrateOpts, Opts};
+use minijudge_rust::error::{Error, Result};
+use minijudge_rust::judge::{VERDICT_AC, VERDICT_TLE};
+use minijudge_rust::languages::LanguageRegistry;
+use minijudge_rust::problem::{solution_path, Metadata, Problem, Solution};
+/// This module contains the calibration of the time limit of a problem with the reference
+/// solutions listed in the metadata.
+use minijudge_rust::sandbox::SandboxPool;
+use minijudge_rust::{Judge, Submission};
+
+/// The verdict and the maximum running time of a reference solution over all testcases.
+pub struct SolutionTiming {
+    pub solution: Solution,
+    pub verdict: String,
+    pub time: f64,
+}
+
+/// Propose a time limit with the usual rule: twice the running time of the slowest solution
+/// expected to get AC, rounded up to 0.1 seconds, which must be below the running time of the
+/// fastest solution expected to get TLE.
+pub fn propose_time_limit(slowest_ac: f64, fastest_tle: Option<f64>) -> Result<f64> {
+    let time_limit = ((slowest_ac * 2. * 10. - 1e-9).ceil() / 10.).max(0.1);
+
+    match fastest_tle {
+        Some(fastest_tle) if time_limit >= fastest_tle => Err(Error::Calibration(format!(
+                "The proposed time limit {} is not below the running time {} of the fastest solution expected to get TLE.",
+                time_limit, fastest_tle
+            ))),
+        _ => Ok(time_limit),
+    }
+}
+
+/// Judge the reference solutions expected to get AC or TLE, print their running time and propose
+/// a time limit.
+pub fn calibrate(
+    opts: &Opts,
+    calibrate_opts: &CalibrateOpts,
+    problem: &Problem,
+    languages: LanguageRegistry,
+    sandboxes: &SandboxPool,
+) -> Result<()> {
+    // Run the solutions with a generous time limit so that slow solutions can be measured.
+    let problem = Problem {
+        metadata: Metadata {
+            time_limit: calibrate_opts.time_limit_cap,
+            wall_time_limit: None,
+            ..problem.metadata.clone()
+        },
+        ..problem.clone()
+    };
+
+    let judge = Judge::builder()
+        .problem(problem)
+        .languages(languages)
+        .sandboxes(sandboxes)
+        .build()?;
+
+    let mut timings: Vec<SolutionTiming> = Vec::new();
+    for solution in judge
+        .problem()
+        .metadata
+        .solutions
+        .iter()
+        .filter(|s| s.expected == VERDICT_AC || s.expected == VERDICT_TLE)
+    {
+        log::info!("Judging solution {}.", solution.source);
+        let judge_output = judge.judge(&Submission {
+            id: None,
+            source: solution_path(opts.metadata_path(), solution),
+            language: solution.language.clone(),
+        })?;
+
+        timings.push(SolutionTiming {
+            solution: solution.clone(),
+            verdict: judge_output.verdict.clone(),
+            time: judge_output.time,
+        });
+    }
+
+    print_report(&timings);
+
+    let failed: Vec<&str> = timings
+        .iter()
+        .filter(|t| t.solution.expected == VERDICT_AC && t.verdict != VERDICT_AC)
+        .map(|t| &t.solution.source[..])
+        .collect();
+    if !failed.is_empty() {
+        return Err(Error::Calibration(format!(
+            "The solutions expected to get AC did not get AC: {}",
+            failed.join(", ")
+        )));
+    }
+
+    let ac_times: Vec<f64> = timings
+        .iter()
+        .filter(|t| t.solution.expected == VERDICT_AC)
+        .map(|t| t.time)
+        .collect();
+    if ac_times.is_empty() {
+        return Err(Error::Calibration(
+            "At least one solution expected to get AC is required.".to_string(),
+        ));
+    }
+    let slowest_ac = ac_times.into_iter().fold(0., f64::max);
+
+    // Solutions expected to get TLE but failing in other ways do not tell anything about timing.
+    let fastest_tle = timings
+        .iter()
+        .filter(|t| t.solution.expected == VERDICT_TLE)
+        .filter(|t| t.verdict == VERDICT_TLE || t.verdict == VERDICT_AC)
+        .map(|t| t.time)
+        .fold(None, |fastest: Option<f64>, time| match fastest {
+            Some(fastest) => Some(fastest.min(time)),
+            None => Some(time),
+        });
+
+    let time_limit = propose_time_limit(slowest_ac, fastest_tle)?;
+    println!("Proposed time limit: {}", time_limit);
+
+    if calibrate_opts.write {
+        write_time_limit(opts.metadata_path(), time_limit)?;
+        log::info!("Time limit written to {}.", opts.metadata_path());
+    }
+
+    Ok(())
+}
+
+fn print_report(timings: &[SolutionTiming]) {
+    println!(
+        "{:<40} {:<8} {:<8} {:>8}",
+        "Solution", "Expected", "Verdict", "Time"
+    );
+    for timing in timings {
+        println!(
+            "{:<40} {:<8} {:<8} {:>8.3}",
+            timing.solution.source, timing.solution.expected, timing.verdict, timing.time
+        );
+    }
+}
+
+/// Write the time limit back to the metadata file. Only the `time_limit` line is edited, so that
+/// the comments and the order of the other fields are kept.
+fn write_time_limit(metadata_path: &str, time_limit: f64) -> Result<()> {
+    let metadata = std::fs::read_to_string(metadata_path)?;
+    std::fs::write(metadata_path, update_time_limit(&metadata, time_limit)?)?;
+
+    Ok(())
+}
+
+/// Replace the value of the top-level `time_limit` key in the metadata, keeping a comment after
+/// the value.
+fn update_time_limit(metadata: &str, time_limit: f64) -> Result<String> {
+    let mut updated = false;
+    let mut lines = vec![];
+    for line in metadata.split_inclusive('\n') {
+        let content = line.trim_end_matches(&['\r', '\n'][..]);
+        match content.strip_prefix("time_limit:") {
+            Some(value) if !updated => {
+                let comment = value.find(" #").map(|i| &value[i..]).unwrap_or_default();
+                lines.push(format!(
+                    "time_limit: {}{}{}",
+                    time_limit,
+                    comment,
+                    &line[content.len()..]
+                ));
+                updated = true;
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    let updated = lines.concat();
+
+    // Make sure that the edited line is the time limit read from the metadata.
+    let value: serde_yaml::Value = serde_yaml::from_str(&updated)?;
+    if value.get("time_limit").and_then(|v| v.as_f64()) != Some(time_limit) {
+        return Err(Error::Calibration(
+            "The time limit cannot be written, since time_limit is not found as a top-level key of the metadata file.".to_string(),
+        ));
+    }
+
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_propose_time_limit() {
+        assert_eq!(propose_time_limit(0.35, Some(2.)).unwrap(), 0.7);
+        assert_eq!(propose_time_limit(0.42, None).unwrap(), 0.9);
+        assert_eq!(propose_time_limit(0.01, None).unwrap(), 0.1);
+
+        // The time limit must be below the fastest TLE solution.
+        assert!(propose_time_limit(0.5, Some(1.)).is_err());
+        assert!(propose_time_limit(0.5, Some(0.8)).is_err());
+    }
+
+    #[test]
+    fn test_update_time_limit() {
+        let metadata = "# Problem A\r\ntime_limit: 2 # seconds\r\nmemory_limit: 262144\r\n";
+        assert_eq!(
+            update_time_limit(metadata, 0.7).unwrap(),
+            "# Problem A\r\ntime_limit: 0.7 # seconds\r\nmemory_limit: 262144\r\n"
+        );
+
+        let metadata = "limits:\n  time_limit: 2\n";
+        assert!(update_time_limit(metadata, 0.7).is_err());
+    }
+}
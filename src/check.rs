@@ -0,0 +1,174 @@
+use minijudge_rust::error::Result;
+use minijudge_rust::languages::{Language, LanguageRegistry, TemplateContext};
+/// This module contains the check of the languages, detecting the version of the toolchain of
+/// each language and compiling and running its hello world program in the sandbox to make sure
+/// that the toolchain works.
+use minijudge_rust::sandbox::{ExecuteConfig, Sandbox, SandboxPool};
+use serde::Serialize;
+
+const EXPECTED_OUTPUT: &str = "Hello, World!";
+
+/// The limits used when checking the languages, which are generous, since only whether the
+/// toolchain works is checked.
+const CHECK_TIME_LIMIT: f64 = 30.;
+const CHECK_MEMORY_LIMIT: i64 = 1048576;
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    /// The language has neither a version command nor a hello world program.
+    Skipped,
+}
+
+/// The result of checking a language.
+#[derive(Serialize)]
+pub struct LanguageReport {
+    pub code: String,
+    pub status: CheckStatus,
+    pub version: Option<String>,
+    /// The problems found, e.g. the compile error of the hello world program.
+    pub problems: Vec<String>,
+}
+
+/// Check each language and print the results, as a table or as JSON. Returns whether all
+/// checked languages work.
+pub fn check(languages: &LanguageRegistry, sandboxes: &SandboxPool, json: bool) -> Result<bool> {
+    let mut reports = vec![];
+    for language in languages.iter() {
+        reports.push(check_language(sandboxes.primary(), language));
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        print_table(&reports);
+    }
+
+    Ok(reports.iter().all(|r| r.status != CheckStatus::Failed))
+}
+
+fn print_table(reports: &[LanguageReport]) {
+    let code_width = reports
+        .iter()
+        .map(|r| r.code.len())
+        .fold("Language".len(), usize::max);
+    println!(
+        "{:<width$} {:<7} Version",
+        "Language",
+        "Result",
+        width = code_width
+    );
+
+    for report in reports {
+        let status = match report.status {
+            CheckStatus::Ok => "OK",
+            CheckStatus::Failed => "FAILED",
+            CheckStatus::Skipped => "skipped",
+        };
+        println!(
+            "{:<width$} {:<7} {}",
+            report.code,
+            status,
+            report.version.as_deref().unwrap_or("-"),
+            width = code_width
+        );
+        for problem in report.problems.iter() {
+            println!("{:<width$}   {}", "", problem, width = code_width);
+        }
+    }
+}
+
+fn check_language(sandbox: &Sandbox, language: &Language) -> LanguageReport {
+    log::info!("Checking language {}.", language.code);
+    let mut report = LanguageReport {
+        code: language.code.clone(),
+        status: CheckStatus::Skipped,
+        version: None,
+        problems: language.missing_programs(),
+    };
+
+    if language.version_command.is_some() {
+        match sandbox.detect_version(language) {
+            Ok(version) => report.version = version,
+            Err(err) => report.problems.push(err.to_string()),
+        }
+        report.status = CheckStatus::Ok;
+    }
+    if let Some(hello_world) = &language.hello_world {
+        // A failure of the sandbox only fails this language.
+        match run_hello_world(sandbox, language, hello_world) {
+            Ok(Some(problem)) => report.problems.push(problem),
+            Ok(None) => {}
+            Err(err) => report.problems.push(format!(
+                "The hello world program failed to be checked: {}",
+                err
+            )),
+        }
+        report.status = CheckStatus::Ok;
+    }
+    if !report.problems.is_empty() {
+        report.status = CheckStatus::Failed;
+    }
+
+    report
+}
+
+/// Compile and run the hello world program, returning the problem found if it does not work.
+fn run_hello_world(
+    sandbox: &Sandbox,
+    language: &Language,
+    hello_world: &str,
+) -> Result<Option<String>> {
+    let (source_file, executable_file) = language.filenames(hello_world)?;
+    sandbox.write_file(&source_file, hello_world)?;
+
+    let config = ExecuteConfig {
+        time_limit: CHECK_TIME_LIMIT,
+        wall_time_limit: CHECK_TIME_LIMIT,
+        memory_limit: CHECK_MEMORY_LIMIT,
+        full_env: true,
+        unlimited_processes: true,
+        ..ExecuteConfig::default()
+    };
+    let context = TemplateContext {
+        memory_limit: CHECK_MEMORY_LIMIT,
+        time_limit: CHECK_TIME_LIMIT,
+        stack_size: language.stack_size(CHECK_MEMORY_LIMIT),
+        ..TemplateContext::new(&source_file, &executable_file, hello_world)
+    };
+    let compile_output = sandbox.compile(language, &config, &context)?;
+    if !compile_output.status.success() {
+        return Ok(Some(format!(
+            "The hello world program failed to compile: {}",
+            String::from_utf8_lossy(&compile_output.stderr).trim()
+        )));
+    }
+
+    let output_file = "hello_world.txt";
+    let run_output = sandbox.run(
+        language,
+        &ExecuteConfig {
+            output_file: Some(output_file),
+            ..config.clone()
+        },
+        &context,
+    )?;
+    if !run_output.status.success() {
+        return Ok(Some(format!(
+            "The hello world program failed to run: {}",
+            String::from_utf8_lossy(&run_output.stderr).trim()
+        )));
+    }
+
+    let output = sandbox.read_file(output_file)?;
+    if output.trim() != EXPECTED_OUTPUT {
+        return Ok(Some(format!(
+            "The hello world program printed {:?}.",
+            output.trim()
+        )));
+    }
+
+    Ok(None)
+}
@@ -1,7 +1,6 @@
-use crate::languages::Language;
 use clap::Clap;
-use serde::{Deserialize, Serialize};
 use log::LevelFilter;
+use minijudge_rust::format::VerdictFormat;
 
 /// MiniJudge-Rust
 /// A miniature judge written in Rust.
@@ -9,50 +8,61 @@ use log::LevelFilter;
 #[clap(version = "0.0-alpha.1", author = "Southball")]
 pub struct Opts {
     /// The path to a YAML file containing the metadata, including time limit, memory limit,
-    /// test counts, etc.
+    /// test counts, etc. Required unless the languages are checked.
     #[clap(long = "metadata")]
-    pub metadata: String,
+    pub metadata: Option<String>,
 
-    /// The language that the source code was written in.
+    /// The language that the source code was written in. If not provided, the language is
+    /// inferred from the extension of the source file.
     #[clap(long = "language")]
-    pub language: String,
+    pub language: Option<String>,
 
-    /// The path to the file containing source code.
+    /// The path to the file containing source code. Required unless a subcommand is given.
     #[clap(long = "source")]
-    pub source: String,
+    pub source: Option<String>,
 
-    /// The path to the source code of checker. The source code must be written in C++.
+    /// The path to the source code of checker. The source code must be written in C++. Required
+    /// unless the languages are checked.
     #[clap(long = "checker")]
-    pub checker: String,
+    pub checker: Option<String>,
 
-    /// The language code for compiling the checker.
+    /// The language code for compiling the checker. Required unless the languages are checked.
     #[clap(long = "checker-language")]
-    pub checker_language: String,
+    pub checker_language: Option<String>,
 
     /// The path to the source code of interactor. If provided, the problem will be treated as
     /// interactive.
     #[clap(long = "interactor")]
     pub interactor: Option<String>,
 
-    /// The path to be used as the base path of the test cases files.
+    /// The path to be used as the base path of the test cases files. Required unless the
+    /// languages are checked.
     #[clap(long = "testcases")]
-    pub testcases: String,
+    pub testcases: Option<String>,
 
-    /// The path to testlib.h.
+    /// The path to testlib.h. Required unless the languages are checked.
     #[clap(long = "testlib")]
-    pub testlib: String,
+    pub testlib: Option<String>,
 
     /// The number of sandboxes to be created. The sandbox ID is 0-based.
-    #[clap(long = "sandboxes")]
+    #[clap(long = "sandboxes", default_value = "1")]
     pub sandboxes: i32,
 
-    /// The format of the verdict to output.
-    #[clap(long = "verdict-format", default_value = "json")]
-    pub verdict_format: String,
+    /// The ID of the first sandbox. Judge processes running simultaneously must be given disjoint
+    /// ranges of sandbox IDs.
+    #[clap(long = "box-id-offset", default_value = "0")]
+    pub box_id_offset: i32,
 
-    /// The file to output the verdict to.
-    #[clap(long = "verdict")]
-    pub verdict: Option<String>,
+    /// The format of the verdict to output: json, yaml, junit, table, markdown, csv or html. If not
+    /// provided, the format of each file given in `--verdict` is inferred from its extension, and
+    /// json is used otherwise.
+    #[clap(long = "verdict-format")]
+    pub verdict_format: Option<VerdictFormat>,
+
+    /// The file to output the verdict to. The option can be given multiple times to output the
+    /// verdict in different formats. If not provided, the verdict is output to standard output.
+    #[clap(long = "verdict", number_of_values = 1)]
+    pub verdict: Vec<String>,
 
     /// The level of verbosity.
     #[clap(short = "v", long = "verbose", parse(from_occurrences))]
@@ -67,66 +77,118 @@ pub struct Opts {
     #[clap(long = "socket")]
     pub socket: Option<String>,
 
+    /// Destination to write updates to as newline-delimited JSON, in addition to the socket. The
+    /// destination is `stdout`, `stderr`, `unix:<path>` for a Unix domain socket, or a file path.
+    /// The option can be given multiple times. With `stdout`, the log is written to standard error.
+    #[clap(long = "progress", number_of_values = 1)]
+    pub progress: Vec<String>,
+
+    /// The folder to cache compiled submissions in. If provided, a submission compiled before with
+    /// the same source, language definition and compiler version is not compiled again.
+    #[clap(long = "compile-cache")]
+    pub compile_cache: Option<String>,
+
     /// The YAML file containing definition to different languages.
     #[clap(long = "languages-definition")]
     pub languages_definition: String,
+
+    #[clap(subcommand)]
+    pub command: Option<Command>,
 }
 
-/// This is the default ID for testcases when no ID is specified.
-fn default_id() -> usize {
-    0
+#[derive(Clap, Clone)]
+pub enum Command {
+    /// Run the reference solutions listed in the metadata and propose a time limit.
+    #[clap(name = "calibrate")]
+    Calibrate(CalibrateOpts),
+
+    /// Judge the reference solutions listed in the metadata and check that each of them gets the
+    /// expected verdict.
+    #[clap(name = "verify")]
+    Verify,
+
+    /// Check the definitions of the languages.
+    #[clap(name = "languages")]
+    Languages(LanguagesOpts),
+
+    /// Judge the submissions listed in a manifest, writing the verdict of each submission and a
+    /// summary to the output folder.
+    #[clap(name = "batch")]
+    Batch(BatchOpts),
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Testcase {
-    #[serde(default = "default_id")]
-    pub id: usize,
-    pub input: String,
-    pub output: String,
+#[derive(Clap, Clone)]
+pub struct BatchOpts {
+    /// The path to a YAML file listing the submissions, each with an `id`, a `source` relative to
+    /// the manifest, and an optional `language`.
+    #[clap(long = "manifest")]
+    pub manifest: String,
+
+    /// The folder to write the verdict of each submission and the summary to.
+    #[clap(long = "output-dir")]
+    pub output_dir: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct Metadata {
-    pub problem_name: String,
-    pub time_limit: f64,
-    pub memory_limit: i64,
-    pub compile_time_limit: f64,
-    pub compile_memory_limit: i64,
-    pub checker_time_limit: f64,
-    pub checker_memory_limit: i64,
-    pub testcases: Vec<Testcase>,
+#[derive(Clap, Clone)]
+pub struct LanguagesOpts {
+    #[clap(subcommand)]
+    pub command: LanguagesCommand,
 }
 
-pub fn read_metadata(metadata_path: &str) -> Result<Metadata, Box<dyn std::error::Error>> {
-    log::debug!("Reading metadata from {}...", &metadata_path);
+#[derive(Clap, Clone)]
+pub enum LanguagesCommand {
+    /// Detect the version and run the hello world program of each language in the sandbox.
+    #[clap(name = "check")]
+    Check(CheckOpts),
+
+    /// Print the definitions of the languages, with the includes and the extends resolved.
+    #[clap(name = "print")]
+    Print,
+}
 
-    let metadata_file = std::fs::File::open(metadata_path)?;
-    let mut metadata: Metadata = match serde_yaml::from_reader(metadata_file) {
-        Ok(metadata) => metadata,
-        Err(err) => {
-            log::error!("Error when parsing metadata: {:?}", err);
-            return Err(Box::new(err));
-        }
-    };
+#[derive(Clap, Clone)]
+pub struct CheckOpts {
+    /// Whether the results should be output as JSON instead of a table.
+    #[clap(long = "json")]
+    pub json: bool,
+}
 
-    for (i, testcase) in metadata.testcases.iter_mut().enumerate() {
-        testcase.id = i;
+impl Opts {
+    /// The path to the metadata file, which is checked to be given before judging.
+    pub fn metadata_path(&self) -> &str {
+        self.metadata.as_deref().unwrap_or_default()
     }
 
-    Ok(metadata)
-}
+    /// The format of the verdict written to the file, or to standard output if the file is `None`.
+    /// An explicit `--verdict-format` takes precedence over the extension of the file.
+    pub fn verdict_format_for(&self, verdict_file: Option<&str>) -> VerdictFormat {
+        self.verdict_format
+            .or_else(|| verdict_file.and_then(VerdictFormat::from_extension))
+            .unwrap_or(VerdictFormat::Json)
+    }
 
-pub fn detect_language(code: &str, languages_definition: &str) -> Result<Language, ()> {
-    let languages_definition = std::fs::File::open(&languages_definition).unwrap();
-    let languages: Vec<Language> = serde_yaml::from_reader(languages_definition).unwrap();
+    /// Whether the progress is written to standard output, in which case nothing else may be
+    /// written there.
+    pub fn progress_to_stdout(&self) -> bool {
+        self.progress.iter().any(|progress| progress == "stdout")
+    }
 
-    for language in languages {
-        if &language.code == code {
-            return Ok(language);
-        }
+    /// Whether the problem is not needed, as only the languages are checked.
+    pub fn checks_languages(&self) -> bool {
+        matches!(self.command, Some(Command::Languages(_)))
     }
+}
 
-    Err(())
+#[derive(Clap, Clone)]
+pub struct CalibrateOpts {
+    /// The time limit (in seconds) used when running the reference solutions. It should be large
+    /// enough for the running time of the solutions expected to get TLE to be measured.
+    #[clap(long = "time-limit-cap", default_value = "10")]
+    pub time_limit_cap: f64,
+
+    /// Whether the proposed time limit should be written back to the metadata file.
+    #[clap(long = "write")]
+    pub write: bool,
 }
 
 pub fn calc_log_level(verbosity: i32, quiet: bool) -> LevelFilter {
@@ -151,8 +213,26 @@ mod tests {
     fn test_calc_log_level() {
         assert_eq!(calc_log_level(2, false), LevelFilter::Debug);
         assert_eq!(calc_log_level(0, true), LevelFilter::Off);
-        
+
         // Quiet option must override all verbosity options.
         assert_eq!(calc_log_level(2, true), LevelFilter::Off);
     }
+
+    #[test]
+    fn test_verdict_format_for() {
+        let args = ["minijudge-rust", "--languages-definition", "languages.yml"];
+        let opts = Opts::parse_from(args);
+        assert_eq!(opts.verdict_format_for(None), VerdictFormat::Json);
+        assert_eq!(
+            opts.verdict_format_for(Some("out.yml")),
+            VerdictFormat::Yaml
+        );
+
+        // An explicit format takes precedence over the extension.
+        let opts = Opts::parse_from([&args[..], &["--verdict-format", "yaml"]].concat());
+        assert_eq!(
+            opts.verdict_format_for(Some("out.json")),
+            VerdictFormat::Yaml
+        );
+    }
 }
@@ -1,10 +1,58 @@
-use serde::{Deserialize, Serialize};
+use crate::judge::TestcaseOutput;
+use serde::Serialize;
 
+pub const EVENT_SUBMISSION_START: &str = "submission_start";
+pub const EVENT_COMPILE_START: &str = "compile_start";
+pub const EVENT_COMPILE_END: &str = "compile_end";
+pub const EVENT_TESTCASE_START: &str = "testcase_start";
 pub const EVENT_TESTCASE: &str = "testcase";
 pub const EVENT_SUBMISSION: &str = "submission";
 
 #[derive(Serialize)]
-pub struct UpdateEvent<'a, T: Serialize + Deserialize<'a>> {
+pub struct UpdateEvent<'a, T: Serialize> {
     pub event_type: String,
+    /// The ID of the submission the event is about, if the submission has one, e.g. when
+    /// judging submissions in a batch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub submission_id: Option<&'a str>,
     pub event: &'a T,
 }
+
+#[derive(Serialize)]
+pub struct SubmissionStartEvent<'a> {
+    pub language: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct CompileStartEvent<'a> {
+    pub language: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct CompileEndEvent<'a> {
+    pub success: bool,
+    pub compile_message: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct TestcaseStartEvent {
+    pub id: usize,
+}
+
+/// The result of a testcase, together with the ID of the testcase.
+#[derive(Serialize)]
+pub struct TestcaseEvent<'a> {
+    pub id: usize,
+    #[serde(flatten)]
+    pub testcase_output: &'a TestcaseOutput,
+}
+
+/// Serialize an event to a single line of JSON.
+pub fn to_json<T: Serialize>(event_type: &str, submission_id: Option<&str>, event: &T) -> String {
+    serde_json::to_string(&UpdateEvent {
+        event_type: event_type.to_string(),
+        submission_id,
+        event,
+    })
+    .unwrap()
+}
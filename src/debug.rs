@@ -1,27 +1,56 @@
-use crate::cli::{Metadata, Opts};
-use crate::judge::Meta;
+use crate::cli::Opts;
+use minijudge_rust::judge::Meta;
+use minijudge_rust::problem::Metadata;
 
 pub fn debug_opts(opts: &Opts) {
     log::debug!("Sandboxes:  {}", &opts.sandboxes);
-    log::debug!("Metadata:   {}", &opts.metadata);
-    log::debug!("Language:   {}", &opts.language);
-    log::debug!("Source:     {}", &opts.source);
-    log::debug!("Checker:    {}", &opts.checker);
+    log::debug!("Box ID offset: {}", &opts.box_id_offset);
+    log::debug!(
+        "Metadata:   {}",
+        &opts.metadata.as_ref().unwrap_or(&"None".to_string())
+    );
+    log::debug!(
+        "Language:   {}",
+        &opts.language.as_ref().unwrap_or(&"None".to_string())
+    );
+    log::debug!(
+        "Source:     {}",
+        &opts.source.as_ref().unwrap_or(&"None".to_string())
+    );
+    log::debug!(
+        "Checker:    {}",
+        &opts.checker.as_ref().unwrap_or(&"None".to_string())
+    );
     log::debug!(
         "Interactor: {}",
         &opts.interactor.as_ref().unwrap_or(&"None".to_string())
     );
-    log::debug!("Testcases:  {}", &opts.testcases);
-    log::debug!("Testlib:    {}", &opts.testlib);
     log::debug!(
-        "Verdict:    {} ({})",
-        &opts.verdict.as_ref().unwrap_or(&"stdout".to_string()),
-        &opts.verdict_format
+        "Testcases:  {}",
+        &opts.testcases.as_ref().unwrap_or(&"None".to_string())
+    );
+    log::debug!(
+        "Testlib:    {}",
+        &opts.testlib.as_ref().unwrap_or(&"None".to_string())
+    );
+    log::debug!(
+        "Verdict:    {} ({:?})",
+        if opts.verdict.is_empty() {
+            "stdout".to_string()
+        } else {
+            opts.verdict.join(", ")
+        },
+        &opts.verdict_format_for(None)
     );
     log::debug!(
         "Socket:     {}",
         &opts.socket.as_ref().unwrap_or(&"None".to_string())
     );
+    log::debug!("Progress:   {}", &opts.progress.join(", "));
+    log::debug!(
+        "Cache:      {}",
+        &opts.compile_cache.as_ref().unwrap_or(&"None".to_string())
+    );
     log::debug!("Lang. Def.: {}", &opts.languages_definition);
 }
 
@@ -31,10 +60,28 @@ pub fn debug_metadata(metadata: &Metadata) {
     log::debug!("Memory limit:         {}", &metadata.memory_limit);
     log::debug!("Compile time limit:   {}", &metadata.compile_time_limit);
     log::debug!("Compile memory limit: {}", &metadata.compile_memory_limit);
+    if let Some(rerun) = &metadata.rerun {
+        log::debug!(
+            "Reruns:               up to {} runs within {}% of time limit",
+            &rerun.max_runs,
+            &rerun.margin
+        );
+    }
     log::debug!("Test cases:");
     for (i, testcase) in metadata.testcases.iter().enumerate() {
         log::debug!("  {}: {} -> {}", i + 1, testcase.input, testcase.output);
     }
+    if !metadata.solutions.is_empty() {
+        log::debug!("Solutions:");
+        for solution in metadata.solutions.iter() {
+            log::debug!(
+                "  {} [{}]: {}",
+                solution.source,
+                solution.language,
+                solution.expected
+            );
+        }
+    }
 }
 
 #[allow(dead_code)]
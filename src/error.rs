@@ -1,32 +1,115 @@
-#[derive(Debug, Clone)]
-pub struct ExecuteError;
+use std::any::Any;
 
-impl std::fmt::Display for ExecuteError {
+/// This is an error in the judging process. Each variant has a machine-readable reason, which is
+/// recorded together with the message when the error results in a system error.
+#[derive(Debug)]
+pub enum Error {
+    /// An error in the options or the definitions passed to the program. The process cannot be
+    /// continued in this case.
+    Option(String),
+    /// An error when reading or writing files, e.g. when copying files into the sandbox.
+    Io(std::io::Error),
+    /// An error when parsing or serializing YAML or JSON.
+    Parse(String),
+    /// The sandbox failed to be created or cleaned up, or failed to run a program.
+    Sandbox(String),
+    /// The meta file of the sandbox is missing after a program is run.
+    MetaMissing,
+    /// The checker failed to compile.
+    CheckerCompile(String),
+    /// The checker failed to run or reported a failure.
+    Checker(String),
+    /// An observer failed to be set up.
+    Observer(String),
+    /// A judge thread panicked.
+    Panic(String),
+    /// An error found when calibrating the time limit with the reference solutions.
+    Calibration(String),
+    /// A template of a command in the languages definition failed to be rendered.
+    Template(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The machine-readable reason of the error.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Error::Option(_) => "option",
+            Error::Io(_) => "io",
+            Error::Parse(_) => "parse",
+            Error::Sandbox(_) => "sandbox",
+            Error::MetaMissing => "meta_missing",
+            Error::CheckerCompile(_) => "checker_compile",
+            Error::Checker(_) => "checker",
+            Error::Observer(_) => "observer",
+            Error::Panic(_) => "panic",
+            Error::Calibration(_) => "calibration",
+            Error::Template(_) => "template",
+        }
+    }
+
+    /// Create the error from the payload of a panic.
+    pub fn from_panic(payload: &(dyn Any + Send)) -> Error {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Unknown panic.".to_string()
+        };
+
+        Error::Panic(message)
+    }
+}
+
+impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Execution error.")
+        match self {
+            Error::Option(message) => write!(f, "Option error: {}", message),
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Parse(message) => write!(f, "Parse error: {}", message),
+            Error::Sandbox(message) => write!(f, "Sandbox error: {}", message),
+            Error::MetaMissing => write!(f, "The meta file of the sandbox is missing."),
+            Error::CheckerCompile(message) => write!(f, "Checker compile error: {}", message),
+            Error::Checker(message) => write!(f, "Checker error: {}", message),
+            Error::Observer(message) => write!(f, "Observer error: {}", message),
+            Error::Panic(message) => write!(f, "Judge thread panicked: {}", message),
+            Error::Calibration(message) => write!(f, "Calibration error: {}", message),
+            Error::Template(message) => write!(f, "Template error: {}", message),
+        }
     }
 }
 
-impl std::error::Error for ExecuteError {
+impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
     }
 }
 
-/// This is an error in the user's option passed to the program. The process cannot be continued in this case.
-#[derive(Debug, Clone)]
-pub struct OptionError {
-    pub message: String,
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
 }
 
-impl std::fmt::Display for OptionError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Option error: {}", self.message)
+impl From<serde_yaml::Error> for Error {
+    fn from(err: serde_yaml::Error) -> Error {
+        Error::Parse(err.to_string())
     }
 }
 
-impl std::error::Error for OptionError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        None
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Parse(err.to_string())
+    }
+}
+
+impl From<zmq::Error> for Error {
+    fn from(err: zmq::Error) -> Error {
+        Error::Observer(err.to_string())
     }
 }
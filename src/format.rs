@@ -0,0 +1,355 @@
+use crate::error::Result;
+use crate::html;
+use crate::judge::{self, JudgeOutput};
+use crate::problem::Problem;
+/// This module contains the formats the verdict of a submission can be output in.
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VerdictFormat {
+    Json,
+    Yaml,
+    /// JUnit XML, for showing the testcases in CI test reports.
+    Junit,
+    /// An aligned table for reading in the terminal.
+    Table,
+    Markdown,
+    Csv,
+    /// A self-contained HTML report for reviewing failing submissions.
+    Html,
+}
+
+impl VerdictFormat {
+    pub const NAMES: &'static [&'static str] =
+        &["json", "yaml", "junit", "table", "markdown", "csv", "html"];
+
+    /// Infer the format from the extension of the file to output the verdict to.
+    pub fn from_extension(path: &str) -> Option<VerdictFormat> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?;
+        match &extension.to_lowercase()[..] {
+            "json" => Some(VerdictFormat::Json),
+            "yaml" | "yml" => Some(VerdictFormat::Yaml),
+            "xml" => Some(VerdictFormat::Junit),
+            "md" => Some(VerdictFormat::Markdown),
+            "csv" => Some(VerdictFormat::Csv),
+            "html" | "htm" => Some(VerdictFormat::Html),
+            _ => None,
+        }
+    }
+
+    /// The extension of the files the verdict is written to in the format, e.g. in batch judging.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VerdictFormat::Json => "json",
+            VerdictFormat::Yaml => "yaml",
+            VerdictFormat::Junit => "xml",
+            VerdictFormat::Table => "txt",
+            VerdictFormat::Markdown => "md",
+            VerdictFormat::Csv => "csv",
+            VerdictFormat::Html => "html",
+        }
+    }
+
+    /// Render the verdict in the format. Colors are only used in the table format, and only if
+    /// `color` is set. The problem is used for showing the limits and the testcases in the HTML
+    /// report.
+    pub fn render(
+        &self,
+        judge_output: &JudgeOutput,
+        problem: Option<&Problem>,
+        color: bool,
+    ) -> Result<String> {
+        Ok(match self {
+            VerdictFormat::Json => serde_json::to_string(judge_output)?,
+            VerdictFormat::Yaml => serde_yaml::to_string(judge_output)?,
+            VerdictFormat::Junit => render_junit(&[("submission", judge_output)]),
+            VerdictFormat::Table => render_table(judge_output, color),
+            VerdictFormat::Markdown => render_markdown(judge_output),
+            VerdictFormat::Csv => render_csv(judge_output),
+            VerdictFormat::Html => html::render_html(judge_output, problem),
+        })
+    }
+}
+
+impl FromStr for VerdictFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<VerdictFormat, String> {
+        match s {
+            "json" => Ok(VerdictFormat::Json),
+            "yaml" => Ok(VerdictFormat::Yaml),
+            "junit" => Ok(VerdictFormat::Junit),
+            "table" => Ok(VerdictFormat::Table),
+            "markdown" => Ok(VerdictFormat::Markdown),
+            "csv" => Ok(VerdictFormat::Csv),
+            "html" => Ok(VerdictFormat::Html),
+            _ => Err(format!(
+                "Unknown verdict format {}. Expected one of: {}.",
+                s,
+                VerdictFormat::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn escape_csv(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Escape a cell of a Markdown table, which must be in a single line.
+fn escape_markdown(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', "<br>")
+}
+
+/// A testcase in a JUnit XML report.
+pub struct JunitCase {
+    pub name: String,
+    pub time: f64,
+    /// The failure of the testcase, or `None` if it passes.
+    pub failure: Option<JunitFailure>,
+}
+
+/// The failure of a testcase in a JUnit XML report.
+pub struct JunitFailure {
+    /// Whether the testcase is reported as an error, e.g. of the judge, instead of a failure.
+    pub error: bool,
+    pub kind: String,
+    pub message: String,
+    pub detail: String,
+}
+
+/// Render a JUnit XML report with a test suite for each named submission. Testcases with SE are
+/// reported as errors, and the other testcases not getting AC as failures.
+pub fn render_junit(suites: &[(&str, &JudgeOutput)]) -> String {
+    let suites: Vec<(&str, Vec<JunitCase>)> = suites
+        .iter()
+        .map(|(name, judge_output)| (*name, junit_cases(judge_output)))
+        .collect();
+
+    render_junit_cases(&suites)
+}
+
+fn junit_cases(judge_output: &JudgeOutput) -> Vec<JunitCase> {
+    judge_output
+        .testcases
+        .iter()
+        .enumerate()
+        .map(|(i, testcase)| {
+            let detail = match &testcase.error {
+                Some(error) => error.message.clone(),
+                None if testcase.verdict == judge::VERDICT_CE
+                    || testcase.verdict == judge::VERDICT_CTLE =>
+                {
+                    judge_output.compile_message.clone()
+                }
+                None => testcase.checker_output.clone(),
+            };
+            let failure = match &testcase.verdict[..] {
+                judge::VERDICT_AC => None,
+                verdict => Some(JunitFailure {
+                    error: verdict == judge::VERDICT_SE,
+                    kind: verdict.to_string(),
+                    message: verdict.to_string(),
+                    detail,
+                }),
+            };
+
+            JunitCase {
+                name: format!("test {}", i + 1),
+                time: testcase.time,
+                failure,
+            }
+        })
+        .collect()
+}
+
+/// Render a JUnit XML report with a test suite for each name and its testcases.
+pub fn render_junit_cases(suites: &[(&str, Vec<JunitCase>)]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+
+    for (name, cases) in suites {
+        let errors = cases
+            .iter()
+            .filter(|c| matches!(&c.failure, Some(failure) if failure.error))
+            .count();
+        let failures = cases.iter().filter(|c| c.failure.is_some()).count() - errors;
+        let time: f64 = cases.iter().map(|c| c.time).sum();
+
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+            escape_xml(name),
+            cases.len(),
+            failures,
+            errors,
+            time
+        ));
+        for case in cases {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape_xml(&case.name),
+                escape_xml(name),
+                case.time
+            ));
+            match &case.failure {
+                Some(failure) => xml.push_str(&format!(
+                    ">\n      <{0} message=\"{1}\" type=\"{2}\">{3}</{0}>\n    </testcase>\n",
+                    if failure.error { "error" } else { "failure" },
+                    escape_xml(&failure.message),
+                    escape_xml(&failure.kind),
+                    escape_xml(&failure.detail)
+                )),
+                None => xml.push_str(" />\n"),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// The ANSI color code of a verdict in the terminal.
+fn verdict_color(verdict: &str) -> &'static str {
+    match verdict {
+        judge::VERDICT_AC => "32",
+        judge::VERDICT_WA | judge::VERDICT_RE => "31",
+        judge::VERDICT_TLE | judge::VERDICT_MLE | judge::VERDICT_OLE => "33",
+        _ => "35",
+    }
+}
+
+fn render_table(judge_output: &JudgeOutput, color: bool) -> String {
+    let paint = |verdict: &str, width: usize| {
+        let cell = format!("{:<width$}", verdict, width = width);
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", verdict_color(verdict), cell)
+        } else {
+            cell
+        }
+    };
+    let verdict_width = judge_output
+        .testcases
+        .iter()
+        .map(|t| t.verdict.len())
+        .fold("Verdict".len(), usize::max);
+
+    let mut table = format!(
+        "{:>4}  {:<width$}  {:>8}  {:>10}  {}\n",
+        "#",
+        "Verdict",
+        "Time",
+        "Memory",
+        "Checker",
+        width = verdict_width
+    );
+    for (i, testcase) in judge_output.testcases.iter().enumerate() {
+        table.push_str(&format!(
+            "{:>4}  {}  {:>8.3}  {:>10}  {}\n",
+            i + 1,
+            paint(&testcase.verdict, verdict_width),
+            testcase.time,
+            testcase.memory,
+            testcase.checker_output.lines().next().unwrap_or("")
+        ));
+    }
+    table.push_str(&format!(
+        "\nVerdict: {}  Time: {:.3}  Memory: {}\n",
+        paint(&judge_output.verdict, 0),
+        judge_output.time,
+        judge_output.memory
+    ));
+    if let Some(error) = &judge_output.error {
+        table.push_str(&format!("Error: {}\n", error.message));
+    }
+
+    table
+}
+
+fn render_markdown(judge_output: &JudgeOutput) -> String {
+    let mut markdown = format!(
+        "**Verdict:** {} | **Time:** {:.3} s | **Memory:** {} KiB\n\n",
+        judge_output.verdict, judge_output.time, judge_output.memory
+    );
+    if let Some(error) = &judge_output.error {
+        markdown.push_str(&format!(
+            "**Error:** {}\n\n",
+            escape_markdown(&error.message)
+        ));
+    }
+
+    markdown.push_str("| # | Verdict | Time (s) | Memory (KiB) | Checker |\n");
+    markdown.push_str("| ---: | --- | ---: | ---: | --- |\n");
+    for (i, testcase) in judge_output.testcases.iter().enumerate() {
+        markdown.push_str(&format!(
+            "| {} | {} | {:.3} | {} | {} |\n",
+            i + 1,
+            testcase.verdict,
+            testcase.time,
+            testcase.memory,
+            escape_markdown(&testcase.checker_output)
+        ));
+    }
+
+    markdown
+}
+
+fn render_csv(judge_output: &JudgeOutput) -> String {
+    let mut csv = String::from("testcase,verdict,time,memory,checker_output\n");
+    for (i, testcase) in judge_output.testcases.iter().enumerate() {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            i + 1,
+            escape_csv(&testcase.verdict),
+            testcase.time,
+            testcase.memory,
+            escape_csv(&testcase.checker_output)
+        ));
+    }
+
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("junit".parse(), Ok(VerdictFormat::Junit));
+        assert!("xml".parse::<VerdictFormat>().is_err());
+        assert_eq!(
+            VerdictFormat::from_extension("report.xml"),
+            Some(VerdictFormat::Junit)
+        );
+        assert_eq!(VerdictFormat::from_extension("verdict.txt"), None);
+    }
+
+    #[test]
+    fn test_render_junit() {
+        let mut judge_output = judge::new_judge_output(2);
+        judge_output.testcases[0].verdict = judge::VERDICT_AC.to_string();
+        judge_output.testcases[1].verdict = judge::VERDICT_WA.to_string();
+        judge_output.testcases[1].checker_output = "wrong answer 1 < 2".to_string();
+
+        let xml = render_junit(&[("main.cpp", &judge_output)]);
+        assert!(xml.contains("tests=\"2\" failures=\"1\" errors=\"0\""));
+        assert!(xml.contains("<failure message=\"WA\" type=\"WA\">wrong answer 1 &lt; 2</failure>"));
+    }
+
+    #[test]
+    fn test_escape_csv() {
+        assert_eq!(escape_csv("ok"), "ok");
+        assert_eq!(escape_csv("a, \"b\""), "\"a, \"\"b\"\"\"");
+    }
+}
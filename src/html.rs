@@ -0,0 +1,274 @@
+use crate::format::escape_xml;
+use crate::judge::{self, Excerpt, JudgeOutput, TestcaseOutput};
+/// This module contains the self-contained HTML report of a submission, for reviewing failing
+/// submissions without reading the verdict JSON.
+use crate::problem::{FeedbackLevel, Problem, Testcase};
+use std::path::PathBuf;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+pre { background: #f6f6f6; padding: 0.5em; margin: 0; max-height: 20em; overflow: auto; }
+details { margin-bottom: 0.5em; }
+.verdict { font-weight: bold; }
+.AC { color: #1a7f37; }
+.WA, .RE { color: #cf222e; }
+.TLE, .MLE, .OLE { color: #9a6700; }
+.SE, .CE, .CTLE { color: #8250df; }
+.diff { background: #ffd7d5; }
+.truncated { color: #888; font-style: italic; }
+";
+
+/// Render the report of the submission. The limits and the input and expected output of the
+/// testcases are only shown if the problem is given.
+pub fn render_html(judge_output: &JudgeOutput, problem: Option<&Problem>) -> String {
+    let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let title = match problem {
+        Some(problem) => format!("Judging report: {}", problem.metadata.problem_name),
+        None => "Judging report".to_string(),
+    };
+    html.push_str(&format!(
+        "<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        escape_xml(&title),
+        STYLE,
+        escape_xml(&title)
+    ));
+
+    html.push_str(&format!(
+        "<p>Verdict: {} &middot; Time: {} &middot; Memory: {}</p>\n",
+        verdict_span(&judge_output.verdict),
+        with_limit(
+            format!("{:.3} s", judge_output.time),
+            problem.map(|p| format!("{} s", p.metadata.time_limit))
+        ),
+        with_limit(
+            format!("{} KiB", judge_output.memory),
+            problem.map(|p| format!("{} KiB", p.metadata.memory_limit))
+        ),
+    ));
+    if let Some(error) = &judge_output.error {
+        html.push_str(&format!(
+            "<p>Error ({}): {}</p>\n",
+            escape_xml(&error.reason),
+            escape_xml(&error.message)
+        ));
+    }
+    if !judge_output.compile_message.is_empty() {
+        html.push_str(&format!(
+            "<details{}><summary>Compile log</summary><pre>{}</pre></details>\n",
+            if judge_output.verdict == judge::VERDICT_CE
+                || judge_output.verdict == judge::VERDICT_CTLE
+            {
+                " open"
+            } else {
+                ""
+            },
+            escape_xml(&judge_output.compile_message)
+        ));
+    }
+
+    html.push_str("<table>\n<tr><th>#</th><th>Verdict</th><th>Time</th><th>Memory</th><th>Checker</th></tr>\n");
+    for (i, testcase) in judge_output.testcases.iter().enumerate() {
+        html.push_str(&format!(
+            "<tr><td><a href=\"#test-{0}\">{0}</a></td><td>{1}</td><td>{2:.3} s</td><td>{3} KiB</td><td>{4}</td></tr>\n",
+            i + 1,
+            verdict_span(&testcase.verdict),
+            testcase.time,
+            testcase.memory,
+            escape_xml(testcase.checker_output.lines().next().unwrap_or(""))
+        ));
+    }
+    html.push_str("</table>\n");
+
+    for (i, testcase) in judge_output.testcases.iter().enumerate() {
+        render_testcase(&mut html, i, testcase, problem);
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn render_testcase(
+    html: &mut String,
+    i: usize,
+    testcase: &TestcaseOutput,
+    problem: Option<&Problem>,
+) {
+    // Failing testcases are expanded, since they are what the report is read for.
+    let open = if testcase.verdict == judge::VERDICT_AC {
+        ""
+    } else {
+        " open"
+    };
+    html.push_str(&format!(
+        "<details id=\"test-{}\"{}><summary>Test {}: {}</summary>\n",
+        i + 1,
+        open,
+        i + 1,
+        verdict_span(&testcase.verdict)
+    ));
+
+    if let Some(detail) = &testcase.detail {
+        html.push_str(&format!("<p>{}</p>\n", escape_xml(detail)));
+    }
+    if !testcase.checker_output.is_empty() {
+        html.push_str(&format!(
+            "<p>Checker:</p><pre>{}</pre>\n",
+            escape_xml(&testcase.checker_output)
+        ));
+    }
+    if let Some(error) = &testcase.error {
+        html.push_str(&format!(
+            "<p>Error:</p><pre>{}</pre>\n",
+            escape_xml(&error.message)
+        ));
+    }
+
+    // The input and the expected output are read from the testcase files if they are not kept in
+    // the verdict, unless they are withheld by the feedback policy.
+    let read = |file: fn(&Testcase) -> &String| {
+        let problem = problem?;
+        let testcase = problem.metadata.testcases.get(i)?;
+        if problem.metadata.feedback.level(testcase) != FeedbackLevel::Full {
+            return None;
+        }
+        Excerpt::read(
+            &PathBuf::from(&problem.testcases).join(file(testcase)),
+            problem.metadata.feedback.excerpt_bytes,
+        )
+        .ok()
+    };
+    let input = testcase.input.clone().or_else(|| read(|t| &t.input));
+    let expected = testcase.answer.clone().or_else(|| read(|t| &t.output));
+
+    html.push_str("<table>\n<tr><th>Input</th><th>Expected output</th><th>Output</th></tr>\n<tr>");
+    html.push_str(&format!(
+        "<td>{}</td>",
+        render_excerpt(input.as_ref(), None)
+    ));
+    html.push_str(&format!(
+        "<td>{}</td>",
+        render_excerpt(expected.as_ref(), None)
+    ));
+    html.push_str(&format!(
+        "<td>{}</td>",
+        render_excerpt(testcase.stdout.as_ref(), expected.as_ref())
+    ));
+    html.push_str("</tr>\n</table>\n");
+    if let Some(stderr) = &testcase.stderr {
+        if stderr.size > 0 {
+            html.push_str(&format!(
+                "<p>Error output:</p>{}\n",
+                render_excerpt(Some(stderr), None)
+            ));
+        }
+    }
+    html.push_str("</details>\n");
+}
+
+/// Render an excerpt of a file. If the expected excerpt is given, the lines different from the
+/// corresponding lines of the expected excerpt are highlighted.
+fn render_excerpt(excerpt: Option<&Excerpt>, expected: Option<&Excerpt>) -> String {
+    let excerpt = match excerpt {
+        Some(excerpt) => excerpt,
+        None => return "<span class=\"truncated\">Not available.</span>".to_string(),
+    };
+
+    let expected_lines: Vec<&str> = match expected {
+        Some(expected) => expected.head.lines().collect(),
+        None => vec![],
+    };
+    let lines: Vec<String> = excerpt
+        .head
+        .lines()
+        .enumerate()
+        .map(|(j, line)| {
+            let differs = expected.is_some()
+                && expected_lines.get(j).map(|l| l.trim_end()) != Some(line.trim_end());
+            if differs {
+                format!("<span class=\"diff\">{}</span>", escape_xml(line))
+            } else {
+                escape_xml(line)
+            }
+        })
+        .collect();
+
+    let mut pre = format!("<pre>{}", lines.join("\n"));
+    if excerpt.is_truncated() {
+        pre.push_str(&format!(
+            "\n<span class=\"truncated\">... ({} bytes in total) ...</span>\n{}",
+            excerpt.size,
+            escape_xml(&excerpt.tail)
+        ));
+    }
+    pre.push_str("</pre>");
+    pre
+}
+
+fn verdict_span(verdict: &str) -> String {
+    format!(
+        "<span class=\"verdict {0}\">{0}</span>",
+        escape_xml(verdict)
+    )
+}
+
+fn with_limit(value: String, limit: Option<String>) -> String {
+    match limit {
+        Some(limit) => format!("{} / {}", value, limit),
+        None => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_excerpt_diff() {
+        let output = Excerpt {
+            head: "1\n3\n".to_string(),
+            tail: "".to_string(),
+            size: 4,
+        };
+        let expected = Excerpt {
+            head: "1\n2\n".to_string(),
+            tail: "".to_string(),
+            size: 4,
+        };
+
+        let html = render_excerpt(Some(&output), Some(&expected));
+        assert_eq!(html, "<pre>1\n<span class=\"diff\">3</span></pre>");
+    }
+
+    #[test]
+    fn test_render_withheld_testcase() {
+        let testcases = std::env::temp_dir().join(format!("minijudge-html-{}", std::process::id()));
+        std::fs::create_dir_all(&testcases).unwrap();
+        std::fs::write(testcases.join("1.in"), "sample input").unwrap();
+        std::fs::write(testcases.join("2.in"), "hidden input").unwrap();
+        std::fs::write(testcases.join("1.out"), "").unwrap();
+        std::fs::write(testcases.join("2.out"), "").unwrap();
+
+        let metadata = serde_yaml::from_str(
+            "problem_name: A\ntime_limit: 1\nwall_time_limit: null\nmemory_limit: 262144\n\
+             compile_time_limit: 10\ncompile_memory_limit: 262144\nchecker_time_limit: 10\n\
+             checker_memory_limit: 262144\nrerun: null\ntestcases:\n\
+             - {input: 1.in, output: 1.out, sample: true}\n\
+             - {input: 2.in, output: 2.out}\n",
+        )
+        .unwrap();
+        let problem = Problem {
+            metadata,
+            testcases: testcases.to_string_lossy().to_string(),
+            checker: "checker.cpp".to_string(),
+            checker_language: "cpp17".to_string(),
+            testlib: "testlib.h".to_string(),
+        };
+
+        let html = render_html(&judge::new_judge_output(2), Some(&problem));
+        std::fs::remove_dir_all(&testcases).unwrap();
+        assert!(html.contains("sample input"));
+        assert!(!html.contains("hidden input"));
+    }
+}
@@ -1,34 +1,286 @@
+use crate::error::Error;
+use crate::problem::{MemoryAccounting, RerunPolicy};
+use serde::{Deserialize, Serialize};
 use std::clone::Clone;
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
 
 pub use judge_definitions::verdicts::*;
 pub use judge_definitions::*;
 
-#[derive(Clone)]
+/// The verdict of a submission writing more output than the output limit.
+pub const VERDICT_OLE: &str = "OLE";
+/// The detail of the OLE verdict, which is kept when the verdict is converted to a shared verdict.
+const OUTPUT_LIMIT_DETAIL: &str = "Output limit exceeded.";
+/// The verdict of a submission whose compilation exceeds the compile time limit.
+pub const VERDICT_CTLE: &str = "CTLE";
+
+/// The reason and the message of a system error.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorDetail {
+    pub reason: String,
+    pub message: String,
+}
+
+impl From<&Error> for ErrorDetail {
+    fn from(err: &Error) -> ErrorDetail {
+        ErrorDetail {
+            reason: err.reason().to_string(),
+            message: err.to_string(),
+        }
+    }
+}
+
+/// The number of bytes kept from each end of a captured file by default.
+pub const DEFAULT_EXCERPT_BYTES: u64 = 1024;
+
+/// The beginning and the end of a file, which may be too large to be kept in full.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Excerpt {
+    pub head: String,
+    /// The end of the file. This is empty if the whole file is kept in `head`.
+    pub tail: String,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+impl Excerpt {
+    /// Read the first and the last `limit` bytes of a file.
+    pub fn read(path: &Path, limit: u64) -> std::io::Result<Excerpt> {
+        Excerpt::from_file(std::fs::File::open(path)?, limit)
+    }
+
+    /// Read the first and the last `limit` bytes of an opened file.
+    pub fn from_file(mut file: std::fs::File, limit: u64) -> std::io::Result<Excerpt> {
+        let size = file.metadata()?.len();
+
+        let mut head = Vec::new();
+        let mut tail = Vec::new();
+        if size <= limit * 2 {
+            file.read_to_end(&mut head)?;
+        } else {
+            (&mut file).take(limit).read_to_end(&mut head)?;
+            file.seek(SeekFrom::End(-(limit as i64)))?;
+            file.read_to_end(&mut tail)?;
+        }
+
+        Ok(Excerpt {
+            head: String::from_utf8_lossy(&head).to_string(),
+            tail: String::from_utf8_lossy(&tail).to_string(),
+            size,
+        })
+    }
+
+    /// Whether part of the file is left out of the excerpt.
+    pub fn is_truncated(&self) -> bool {
+        !self.tail.is_empty()
+    }
+
+    /// The excerpt as text, with the part left out marked.
+    pub fn to_text(&self) -> String {
+        if self.is_truncated() {
+            format!(
+                "{}\n... ({} bytes in total) ...\n{}",
+                self.head, self.size, self.tail
+            )
+        } else {
+            self.head.clone()
+        }
+    }
+}
+
+/// The result of compiling the submission.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompileOutput {
+    pub time: f64,
+    pub memory: i64,
+    pub stdout: Excerpt,
+    pub stderr: Excerpt,
+    /// The details of the compilation failing, e.g. the time limit being exceeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl CompileOutput {
+    pub fn new(meta: &Meta, stdout: Excerpt, stderr: Excerpt) -> CompileOutput {
+        CompileOutput {
+            time: meta.time.unwrap_or(0.),
+            memory: meta.memory.unwrap_or(0),
+            stdout,
+            stderr,
+            detail: meta.detail.clone().or_else(|| meta.message.clone()),
+        }
+    }
+
+    /// The compile message shown to the contestant: the output followed by the error output of
+    /// the compiler, each truncated.
+    pub fn message(&self) -> String {
+        [self.stdout.to_text(), self.stderr.to_text()]
+            .iter()
+            .map(|text| text.trim_end())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+/// The verdict of a submission failing to compile, or `None` if it compiles.
+pub fn compile_verdict(meta: &Meta, success: bool) -> Option<&'static str> {
+    if meta.verdict.as_deref() == Some(VERDICT_TLE) {
+        Some(VERDICT_CTLE)
+    } else if success {
+        None
+    } else {
+        Some(VERDICT_CE)
+    }
+}
+
+/// The output of a testcase. This extends `judge_definitions::TestcaseOutput` with optional
+/// fields, keeping the fields of the shared definition, so that the serialized output can still be
+/// read as the shared definition by the frontends.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TestcaseOutput {
+    pub verdict: String,
+    pub time: f64,
+    pub memory: i64,
+    pub checker_output: String,
+    pub sandbox_output: String,
+    /// The CPU time of every run of the testcase, in the order the runs were made.
+    #[serde(default)]
+    pub time_measurements: Vec<f64>,
+    /// The details of the error if the verdict is a system error.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorDetail>,
+    /// The details of the verdict, e.g. the signal killing the program.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The excerpt of the output of the program.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout: Option<Excerpt>,
+    /// The excerpt of the error output of the program.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<Excerpt>,
+    /// The excerpt of the input of the testcase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<Excerpt>,
+    /// The excerpt of the expected answer of the testcase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<Excerpt>,
+}
+
+/// The output of a submission. This extends `judge_definitions::JudgeOutput` in the same way as
+/// `TestcaseOutput`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JudgeOutput {
+    pub verdict: String,
+    pub time: f64,
+    pub memory: i64,
+    pub compile_message: String,
+    pub testcases: Vec<TestcaseOutput>,
+    /// The method used for measuring the memory usage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_accounting: Option<MemoryAccounting>,
+    /// The memory usage (in KB) of the runtime of the language subtracted from the memory usage.
+    #[serde(default)]
+    pub memory_baseline: i64,
+    /// The details of the error if the verdict is a system error. For system errors in a
+    /// testcase, this is the error of the first such testcase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorDetail>,
+    /// The result of compiling the submission, if it is compiled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compile: Option<CompileOutput>,
+    /// The versions of the toolchains of the submission and the checker, by language code.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub versions: BTreeMap<String, String>,
+}
+
+/// The verdict of judge-definitions standing for a verdict. The verdicts specific to this judge
+/// are mapped to the closest shared verdict, and are kept in the `detail` of the output only.
+pub fn shared_verdict(verdict: &str) -> &str {
+    match verdict {
+        VERDICT_OLE => VERDICT_RE,
+        VERDICT_CTLE => VERDICT_CE,
+        verdict => verdict,
+    }
+}
+
+impl From<&TestcaseOutput> for judge_definitions::TestcaseOutput {
+    fn from(testcase: &TestcaseOutput) -> judge_definitions::TestcaseOutput {
+        judge_definitions::TestcaseOutput {
+            verdict: shared_verdict(&testcase.verdict).to_string(),
+            time: testcase.time,
+            memory: testcase.memory,
+            checker_output: testcase.checker_output.clone(),
+            sandbox_output: testcase.sandbox_output.clone(),
+        }
+    }
+}
+
+impl From<&JudgeOutput> for judge_definitions::JudgeOutput {
+    fn from(judge_output: &JudgeOutput) -> judge_definitions::JudgeOutput {
+        judge_definitions::JudgeOutput {
+            verdict: shared_verdict(&judge_output.verdict).to_string(),
+            time: judge_output.time,
+            memory: judge_output.memory,
+            compile_message: judge_output.compile_message.clone(),
+            testcases: judge_output.testcases.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// The information in the meta file written by isolate after running a program.
+#[derive(Clone, Default)]
 pub struct Meta {
     pub time: Option<f64>,
     pub time_wall: Option<f64>,
+    /// The maximum resident set size (in KB) of the program.
     pub memory: Option<i64>,
+    /// The peak memory usage (in KB) of the control group of the program.
+    pub cg_memory: Option<i64>,
     pub exit_code: Option<i64>,
+    pub exit_signal: Option<i64>,
+    /// Whether the program is killed by the sandbox.
+    pub killed: bool,
+    /// Whether the program is killed by the out-of-memory killer of the control group.
+    pub oom_killed: bool,
+    /// The human-readable status message of the sandbox.
+    pub message: Option<String>,
+    pub csw_voluntary: Option<i64>,
+    pub csw_forced: Option<i64>,
     pub verdict: Option<String>,
+    /// The details of the verdict, e.g. the signal killing the program.
+    pub detail: Option<String>,
 }
 
 fn map_status(raw_status: &str) -> String {
     match raw_status {
-        "RE" => VERDICT_RE.to_string(),
-        "SG" => VERDICT_MLE.to_string(),
+        "RE" | "SG" => VERDICT_RE.to_string(),
         "TO" => VERDICT_TLE.to_string(),
         _ => VERDICT_SE.to_string(),
     }
 }
 
-pub fn parse_meta(source: &str) -> Meta {
-    let mut meta = Meta {
-        time: None,
-        time_wall: None,
-        memory: None,
-        verdict: None,
-        exit_code: None,
+/// Describe a signal killing the program.
+fn signal_detail(signal: i64) -> String {
+    let name = match signal as libc::c_int {
+        libc::SIGILL => "SIGILL (illegal instruction)",
+        libc::SIGABRT => "SIGABRT (aborted, e.g. by a failed assertion or an uncaught exception)",
+        libc::SIGBUS => "SIGBUS (bus error)",
+        libc::SIGFPE => "SIGFPE (floating point exception, e.g. division by zero)",
+        libc::SIGKILL => "SIGKILL (killed)",
+        libc::SIGSEGV => {
+            "SIGSEGV (segmentation fault, e.g. invalid memory access or stack overflow)"
+        }
+        _ => return format!("Killed by signal {}.", signal),
     };
+    format!("Killed by signal {} {}.", signal, name)
+}
+
+pub fn parse_meta(source: &str) -> Meta {
+    let mut meta = Meta::default();
+    let mut status: Option<&str> = None;
 
     let lines: Vec<&str> = source.split('\n').collect();
 
@@ -54,12 +306,42 @@ pub fn parse_meta(source: &str) -> Meta {
                         meta.memory = Some(v);
                     }
                 }
+                "cg-mem" => {
+                    if let Ok(v) = value.parse::<i64>() {
+                        meta.cg_memory = Some(v);
+                    }
+                }
                 "exitcode" => {
                     if let Ok(v) = value.parse::<i64>() {
                         meta.exit_code = Some(v);
                     }
                 }
+                "exitsig" => {
+                    if let Ok(v) = value.parse::<i64>() {
+                        meta.exit_signal = Some(v);
+                    }
+                }
+                "killed" => {
+                    meta.killed = value == "1";
+                }
+                "cg-oom-killed" => {
+                    meta.oom_killed = value == "1";
+                }
+                "message" => {
+                    meta.message = Some(value.to_string());
+                }
+                "csw-voluntary" => {
+                    if let Ok(v) = value.parse::<i64>() {
+                        meta.csw_voluntary = Some(v);
+                    }
+                }
+                "csw-forced" => {
+                    if let Ok(v) = value.parse::<i64>() {
+                        meta.csw_forced = Some(v);
+                    }
+                }
                 "status" => {
+                    status = Some(value);
                     meta.verdict = Some(map_status(value));
                 }
                 _ => {}
@@ -67,9 +349,110 @@ pub fn parse_meta(source: &str) -> Meta {
         }
     }
 
+    match (status, meta.exit_signal, meta.exit_code) {
+        // The sandbox fails by itself, and the message tells the reason.
+        (Some("XX"), _, _) => meta.detail = meta.message.clone(),
+        (_, Some(signal), _) => meta.detail = Some(signal_detail(signal)),
+        (Some("RE"), None, Some(exit_code)) => {
+            meta.detail = Some(format!("Exited with code {}.", exit_code))
+        }
+        _ => {}
+    }
+
+    if meta.oom_killed {
+        meta.verdict = Some(VERDICT_MLE.to_string());
+        meta.detail = Some("Killed by the out-of-memory killer.".to_string());
+    }
+
+    // The program is killed by SIGXFSZ when it writes more than the output limit.
+    if meta.exit_signal == Some(libc::SIGXFSZ as i64) {
+        meta.verdict = Some(VERDICT_OLE.to_string());
+        meta.detail = Some(OUTPUT_LIMIT_DETAIL.to_string());
+    }
+
+    meta
+}
+
+/// Set the memory usage of the run measured with the method, less the baseline of the language.
+pub fn account_memory(meta: &Meta, accounting: MemoryAccounting, baseline: i64) -> Meta {
+    let mut meta = meta.clone();
+
+    let memory = match accounting {
+        MemoryAccounting::Rss => meta.memory,
+        // The max-rss is the best estimate if the control group is not available.
+        MemoryAccounting::Cgroup => meta.cg_memory.or(meta.memory),
+    };
+    meta.memory = memory.map(|memory| (memory - baseline).max(0));
+
+    meta
+}
+
+/// Mark the run as exceeding the memory limit (in KB) if its memory usage, or the memory usage of
+/// its control group, reached the limit, e.g. when the program crashes because an allocation
+/// fails. Runs exceeding the time or output limit keep their verdict.
+pub fn apply_memory_limit(meta: &Meta, memory_limit: i64) -> Meta {
+    let mut meta = meta.clone();
+
+    let exceeded = [meta.memory, meta.cg_memory]
+        .iter()
+        .flatten()
+        .any(|&memory| memory >= memory_limit);
+    let overridable = match &meta.verdict {
+        Some(verdict) => verdict == VERDICT_RE,
+        None => true,
+    };
+    if exceeded && overridable {
+        meta.verdict = Some(VERDICT_MLE.to_string());
+    }
+
+    meta
+}
+
+/// Explain a time limit exceeded by the wall time rather than the CPU time, which means that the
+/// program was idle, e.g. waiting for input or sleeping.
+pub fn apply_wall_time_limit(meta: &Meta, time_limit: f64) -> Meta {
+    let mut meta = meta.clone();
+
+    if meta.verdict == Some(VERDICT_TLE.to_string()) {
+        if let (Some(time), Some(time_wall)) = (meta.time, meta.time_wall) {
+            if time < time_limit {
+                meta.detail = Some(format!(
+                    "Wall time limit exceeded after {:.3} s with {:.3} s of CPU time. The program was idle, e.g. waiting for input or sleeping.",
+                    time_wall, time
+                ));
+            }
+        }
+    }
+
+    meta
+}
+
+/// Mark the run as exceeding the output limit if its output is larger than the limit (in KB), e.g.
+/// when the program handles SIGXFSZ and fails on the write instead of being killed. Output of
+/// exactly the limit is accepted.
+pub fn apply_output_limit(meta: &Meta, output_size: u64, output_limit: i64) -> Meta {
+    let mut meta = meta.clone();
+
+    if output_size > output_limit as u64 * 1024 && meta.verdict != Some(VERDICT_TLE.to_string()) {
+        meta.verdict = Some(VERDICT_OLE.to_string());
+        meta.detail = Some(OUTPUT_LIMIT_DETAIL.to_string());
+    }
+
     meta
 }
 
+/// Decide whether a run should be repeated to reduce timing noise. Only runs that exceeded the
+/// time limit, or finished within the margin of the time limit, are repeated.
+pub fn should_rerun(meta: &Meta, time_limit: f64, policy: &RerunPolicy) -> bool {
+    match &meta.verdict {
+        Some(verdict) => verdict == VERDICT_TLE,
+        None => match meta.time {
+            Some(time) => time >= time_limit * (1. - policy.margin / 100.),
+            None => false,
+        },
+    }
+}
+
 pub fn apply_checker_output(meta: &Meta, checker_output: &str) -> Meta {
     let mut meta = meta.clone();
 
@@ -84,6 +467,61 @@ pub fn apply_checker_output(meta: &Meta, checker_output: &str) -> Meta {
     meta
 }
 
+/// Create the output of a submission waiting for judge.
+pub fn new_judge_output(testcase_count: usize) -> JudgeOutput {
+    JudgeOutput {
+        verdict: VERDICT_WJ.to_string(),
+        time: 0.0,
+        memory: 0,
+        compile_message: "".into(),
+        testcases: vec![
+            TestcaseOutput {
+                verdict: VERDICT_WJ.to_string(),
+                time: 0.0,
+                memory: 0,
+                checker_output: "".to_string(),
+                sandbox_output: "".to_string(),
+                time_measurements: vec![],
+                error: None,
+                detail: None,
+                stdout: None,
+                stderr: None,
+                input: None,
+                answer: None,
+            };
+            testcase_count
+        ],
+        memory_accounting: None,
+        memory_baseline: 0,
+        error: None,
+        compile: None,
+        versions: BTreeMap::new(),
+    }
+}
+
+/// Create the output of a submission which cannot be judged because of the error.
+pub fn system_error_output(testcase_count: usize, err: &Error) -> JudgeOutput {
+    let mut judge_output = new_judge_output(testcase_count);
+    fill_verdict(&mut judge_output, VERDICT_SE);
+    judge_output.error = Some(err.into());
+    judge_output
+}
+
+/// Mark a testcase as a system error caused by the error.
+pub fn set_system_error(testcase_output: &mut TestcaseOutput, err: &Error) {
+    testcase_output.verdict = VERDICT_SE.to_string();
+    testcase_output.error = Some(err.into());
+}
+
+/// Set the verdict of the submission and all its testcases, e.g. when the submission cannot be run
+/// at all.
+pub fn fill_verdict(judge_output: &mut JudgeOutput, verdict: &str) {
+    judge_output.verdict = verdict.to_string();
+    for testcase in judge_output.testcases.iter_mut() {
+        testcase.verdict = verdict.to_string();
+    }
+}
+
 pub fn calc_overall_verdict(judge_output: &mut JudgeOutput) {
     judge_output.time = judge_output
         .testcases
@@ -94,7 +532,7 @@ pub fn calc_overall_verdict(judge_output: &mut JudgeOutput) {
         .testcases
         .iter()
         .map(|t| t.memory)
-        .fold(std::i64::MIN, i64::max);
+        .fold(i64::MIN, i64::max);
     judge_output.verdict = match judge_output
         .testcases
         .iter()
@@ -104,4 +542,213 @@ pub fn calc_overall_verdict(judge_output: &mut JudgeOutput) {
         Some(v) => v.clone(),
         None => VERDICT_AC.to_string(),
     };
+    if judge_output.error.is_none() {
+        judge_output.error = judge_output.testcases.iter().find_map(|t| t.error.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_rerun() {
+        let policy = RerunPolicy {
+            max_runs: 3,
+            margin: 10.,
+        };
+
+        let meta = parse_meta("time:0.950\ntime-wall:0.990\nmax-rss:1024\n");
+        assert!(should_rerun(&meta, 1., &policy));
+
+        let meta = parse_meta("time:0.500\ntime-wall:0.520\nmax-rss:1024\n");
+        assert!(!should_rerun(&meta, 1., &policy));
+
+        let meta = parse_meta("time:1.010\ntime-wall:1.050\nstatus:TO\n");
+        assert!(should_rerun(&meta, 1., &policy));
+
+        // Runtime errors are not caused by timing noise.
+        let meta = parse_meta("time:0.990\ntime-wall:1.000\nstatus:RE\n");
+        assert!(!should_rerun(&meta, 1., &policy));
+    }
+
+    #[test]
+    fn test_shared_definitions() {
+        let mut judge_output = system_error_output(1, &Error::MetaMissing);
+        judge_output.testcases[0].detail = Some("SIGSEGV".to_string());
+
+        let json = serde_json::to_string(&judge_output).unwrap();
+        let shared: judge_definitions::JudgeOutput = serde_json::from_str(&json).unwrap();
+        assert_eq!(shared.verdict, VERDICT_SE);
+        assert_eq!(shared.testcases[0].verdict, VERDICT_SE);
+        assert_eq!(
+            serde_json::to_value(&shared).unwrap(),
+            serde_json::to_value(judge_definitions::JudgeOutput::from(&judge_output)).unwrap()
+        );
+
+        // The verdicts specific to this judge are converted to the shared verdicts.
+        judge_output.testcases[0].verdict = VERDICT_OLE.to_string();
+        let shared = judge_definitions::TestcaseOutput::from(&judge_output.testcases[0]);
+        assert_eq!(shared.verdict, VERDICT_RE);
+        judge_output.verdict = VERDICT_CTLE.to_string();
+        let shared = judge_definitions::JudgeOutput::from(&judge_output);
+        assert_eq!(shared.verdict, VERDICT_CE);
+    }
+
+    #[test]
+    fn test_system_error_output() {
+        let judge_output = system_error_output(2, &Error::MetaMissing);
+        assert_eq!(judge_output.verdict, VERDICT_SE);
+        assert!(judge_output
+            .testcases
+            .iter()
+            .all(|t| t.verdict == VERDICT_SE));
+        assert_eq!(judge_output.error.unwrap().reason, "meta_missing");
+    }
+
+    #[test]
+    fn test_excerpt_read() {
+        let path = std::env::temp_dir().join(format!("minijudge-excerpt-{}", std::process::id()));
+        std::fs::write(&path, "0123456789").unwrap();
+
+        let excerpt = Excerpt::read(&path, 3).unwrap();
+        assert_eq!(excerpt.head, "012");
+        assert_eq!(excerpt.tail, "789");
+        assert_eq!(excerpt.size, 10);
+        assert!(excerpt.is_truncated());
+
+        let excerpt = Excerpt::read(&path, 5).unwrap();
+        assert_eq!(excerpt.head, "0123456789");
+        assert!(!excerpt.is_truncated());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let meta = parse_meta(&format!(
+            "time:0.100\ntime-wall:0.120\nexitsig:{}\nstatus:SG\n",
+            libc::SIGXFSZ
+        ));
+        assert_eq!(meta.verdict, Some(VERDICT_OLE.to_string()));
+
+        let meta = parse_meta("time:0.100\ntime-wall:0.120\nexitcode:1\nstatus:RE\n");
+        let meta = apply_output_limit(&meta, 1024 * 1024 + 1, 1024);
+        assert_eq!(meta.verdict, Some(VERDICT_OLE.to_string()));
+
+        let meta = parse_meta("time:0.100\ntime-wall:0.120\n");
+        assert_eq!(apply_output_limit(&meta, 1024 * 1024, 1024).verdict, None);
+    }
+
+    #[test]
+    fn test_parse_meta_signal() {
+        let meta = parse_meta("time:0.010\ntime-wall:0.020\nmax-rss:1024\nexitsig:11\nkilled:1\nstatus:SG\nmessage:Caught fatal signal 11\n");
+        assert_eq!(meta.verdict, Some(VERDICT_RE.to_string()));
+        assert!(meta.killed);
+        assert!(meta.detail.unwrap().contains("SIGSEGV"));
+
+        let meta = parse_meta(
+            "time:0.010\ntime-wall:0.020\ncg-mem:262144\ncg-oom-killed:1\nexitsig:9\nstatus:SG\n",
+        );
+        assert_eq!(meta.verdict, Some(VERDICT_MLE.to_string()));
+        assert_eq!(meta.cg_memory, Some(262144));
+
+        let meta = parse_meta("status:XX\nmessage:Cannot run proxy\n");
+        assert_eq!(meta.verdict, Some(VERDICT_SE.to_string()));
+        assert_eq!(meta.detail, Some("Cannot run proxy".to_string()));
+    }
+
+    #[test]
+    fn test_apply_memory_limit() {
+        let meta = parse_meta("time:0.010\nmax-rss:262144\nexitsig:6\nstatus:SG\n");
+        assert_eq!(
+            apply_memory_limit(&meta, 262144).verdict,
+            Some(VERDICT_MLE.to_string())
+        );
+
+        let meta = parse_meta("time:1.010\nmax-rss:262144\nstatus:TO\n");
+        assert_eq!(
+            apply_memory_limit(&meta, 262144).verdict,
+            Some(VERDICT_TLE.to_string())
+        );
+    }
+
+    #[test]
+    fn test_allocation_failure() {
+        // The program aborts when an allocation fails at the limit of its control group, while
+        // its resident set is still below the limit.
+        let meta = parse_meta("time:0.200\nmax-rss:250000\ncg-mem:262144\nexitsig:6\nstatus:SG\n");
+        assert_eq!(meta.verdict, Some(VERDICT_RE.to_string()));
+        let meta = account_memory(&meta, MemoryAccounting::Rss, 0);
+        assert_eq!(meta.memory, Some(250000));
+        assert_eq!(
+            apply_memory_limit(&meta, 262144).verdict,
+            Some(VERDICT_MLE.to_string())
+        );
+
+        // A crash below the limit is still a runtime error.
+        let meta = parse_meta("time:0.200\nmax-rss:1024\ncg-mem:2048\nexitsig:11\nstatus:SG\n");
+        assert_eq!(
+            apply_memory_limit(&meta, 262144).verdict,
+            Some(VERDICT_RE.to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_wall_time_limit() {
+        let meta = parse_meta("time:0.002\ntime-wall:3.001\nkilled:1\nstatus:TO\n");
+        let meta = apply_wall_time_limit(&meta, 1.);
+        assert_eq!(meta.verdict, Some(VERDICT_TLE.to_string()));
+        assert!(meta.detail.unwrap().contains("idle"));
+
+        let meta = parse_meta("time:1.001\ntime-wall:1.010\nkilled:1\nstatus:TO\n");
+        assert_eq!(apply_wall_time_limit(&meta, 1.).detail, None);
+    }
+
+    #[test]
+    fn test_account_memory() {
+        let meta = parse_meta("time:0.100\nmax-rss:30000\ncg-mem:120000\n");
+        assert_eq!(
+            account_memory(&meta, MemoryAccounting::Rss, 0).memory,
+            Some(30000)
+        );
+        assert_eq!(
+            account_memory(&meta, MemoryAccounting::Cgroup, 40000).memory,
+            Some(80000)
+        );
+
+        let meta = parse_meta("time:0.100\nmax-rss:30000\n");
+        assert_eq!(
+            account_memory(&meta, MemoryAccounting::Cgroup, 40000).memory,
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_compile_output() {
+        let meta =
+            parse_meta("time:10.002\nmax-rss:51200\nstatus:TO\nmessage:Time limit exceeded\n");
+        assert_eq!(compile_verdict(&meta, false), Some(VERDICT_CTLE));
+        assert_eq!(compile_verdict(&Meta::default(), false), Some(VERDICT_CE));
+        assert_eq!(compile_verdict(&Meta::default(), true), None);
+
+        let excerpt = |head: &str, tail: &str, size| Excerpt {
+            head: head.to_string(),
+            tail: tail.to_string(),
+            size,
+        };
+        let compile_output = CompileOutput::new(
+            &meta,
+            excerpt("", "", 0),
+            excerpt("error: a\n", "error: z\n", 4096),
+        );
+        assert_eq!(
+            compile_output.detail.as_deref(),
+            Some("Time limit exceeded")
+        );
+        assert_eq!(
+            compile_output.message(),
+            "error: a\n\n... (4096 bytes in total) ...\nerror: z"
+        );
+    }
 }
@@ -1,8 +1,115 @@
+use crate::error::{Error, Result};
 use handlebars::Handlebars;
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+/// A resource limit which may be lifted, written as a number or `unlimited` in the definition.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "RawLimit", into = "RawLimit")]
+pub enum Limit {
+    Unlimited,
+    Value(u64),
+}
 
 #[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawLimit {
+    Value(u64),
+    Text(String),
+}
+
+impl TryFrom<RawLimit> for Limit {
+    type Error = String;
+
+    fn try_from(raw: RawLimit) -> std::result::Result<Limit, String> {
+        match raw {
+            RawLimit::Value(0) => Err("A limit must be positive, or `unlimited`.".to_string()),
+            RawLimit::Value(value) => Ok(Limit::Value(value)),
+            RawLimit::Text(text) if text == "unlimited" => Ok(Limit::Unlimited),
+            RawLimit::Text(text) => Err(format!(
+                "Invalid limit {}. A limit must be a number or `unlimited`.",
+                text
+            )),
+        }
+    }
+}
+
+impl From<Limit> for RawLimit {
+    fn from(limit: Limit) -> RawLimit {
+        match limit {
+            Limit::Unlimited => RawLimit::Text("unlimited".to_string()),
+            Limit::Value(value) => RawLimit::Value(value),
+        }
+    }
+}
+
+/// The options of a directory rule, as supported by isolate.
+const DIR_OPTIONS: &[&str] = &["rw", "dev", "noexec", "maybe", "fs", "tmp", "norec"];
+
+/// A directory made available inside the sandbox.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DirRule {
+    /// The path inside the sandbox, which must be absolute.
+    pub inside: String,
+    /// The path outside the sandbox, which is the same as the path inside if not specified.
+    pub outside: Option<String>,
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+/// The resources available to programs in a language when they are run, translated to the flags
+/// of the sandbox.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RuntimeSettings {
+    /// The stack size (in KB), e.g. `unlimited` for deep recursion in C++.
+    pub stack: Option<Limit>,
+    /// The maximum number of processes and threads, e.g. `unlimited` for runtimes with multiple
+    /// threads such as the JVM and Node.js.
+    pub processes: Option<Limit>,
+    /// The maximum number of open files.
+    pub open_files: Option<Limit>,
+    /// The environment variables set for the program.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The extra directories made available to the program.
+    #[serde(default)]
+    pub dirs: Vec<DirRule>,
+}
+
+impl RuntimeSettings {
+    /// Check the settings which cannot be checked when parsing.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        for name in self.env.keys() {
+            if name.is_empty() || name.contains('=') {
+                return Err(format!("Invalid environment variable name {:?}.", name));
+            }
+        }
+
+        for dir in self.dirs.iter() {
+            if !dir.inside.starts_with('/') {
+                return Err(format!(
+                    "The directory {} inside the sandbox must be an absolute path.",
+                    dir.inside
+                ));
+            }
+            if let Some(option) = dir.options.iter().find(|o| !DIR_OPTIONS.contains(&&o[..])) {
+                return Err(format!(
+                    "Unknown option {} of the directory {}. Expected one of: {}.",
+                    option,
+                    dir.inside,
+                    DIR_OPTIONS.join(", ")
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Language {
     pub source_filename: String,
     pub executable_filename: String,
@@ -11,31 +118,794 @@ pub struct Language {
     pub execute_command: Vec<String>,
     pub compile_flags: Option<Vec<String>>,
     pub execute_flags: Option<Vec<String>>,
+    /// The memory usage (in KB) of an empty program in the language, e.g. of the runtime, which
+    /// is subtracted from the memory usage of submissions.
+    #[serde(default)]
+    pub memory_baseline: i64,
+    /// The resources available to programs in the language when they are run.
+    #[serde(default)]
+    pub runtime: RuntimeSettings,
+    /// The command printing the version of the toolchain, e.g. `g++ --version`.
+    pub version_command: Option<Vec<String>>,
+    /// The extensions of the source files in the language, e.g. `cpp`, for inferring the language
+    /// of a source file.
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// A program printing `Hello, World!`, for checking the toolchain of the language.
+    pub hello_world: Option<String>,
+}
+
+/// The values passed to the templates of the compile and execute commands.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct TemplateContext {
+    pub source: String,
+    pub destination: String,
+    pub executable: String,
+    /// The filename of the source without its extension.
+    pub source_basename: String,
+    /// The name of the public top-level class detected from the source, or `Main` if there is no
+    /// such class.
+    pub main_class: String,
+    /// The memory limit (in KB) of the program.
+    pub memory_limit: i64,
+    /// The time limit (in seconds) of the program.
+    pub time_limit: f64,
+    /// The stack size (in KB) of the program, the memory limit if the stack is unlimited.
+    pub stack_size: i64,
+    /// The filenames of the extra files copied next to the source.
+    pub extra_files: Vec<String>,
+}
+
+impl TemplateContext {
+    /// Create the context for compiling the source into the destination and running it. The
+    /// source code is used for detecting the main class. The limits are left as zero.
+    pub fn new(source: &str, destination: &str, source_code: &str) -> TemplateContext {
+        let source_basename = std::path::Path::new(source)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        TemplateContext {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            executable: destination.to_string(),
+            main_class: detect_main_class(source_code)
+                .unwrap_or_else(|| DEFAULT_MAIN_CLASS.to_string()),
+            source_basename,
+            ..TemplateContext::default()
+        }
+    }
+}
+
+/// The main class assumed when no class is found in the source.
+const DEFAULT_MAIN_CLASS: &str = "Main";
+
+/// Detect the name of the main class in a Java or Kotlin source, e.g. `Main` in
+/// `public final class Main {`. The public class is preferred over the first class declared. Only
+/// top-level declarations are considered, so nested classes are never chosen.
+pub fn detect_main_class(source_code: &str) -> Option<String> {
+    const MODIFIERS: &[&str] = &["public", "final", "abstract", "static", "strictfp", "open"];
+
+    let mut first_class = None;
+    for line in top_level_code(source_code).lines() {
+        let mut tokens = line.split_whitespace();
+        let mut public = false;
+        while let Some(token) = tokens.next() {
+            if token == "public" {
+                public = true;
+            } else if token == "class" || token == "object" {
+                let name: String = tokens
+                    .next()
+                    .unwrap_or("")
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '$')
+                    .collect();
+                if name.is_empty() {
+                    break;
+                }
+                if public {
+                    return Some(name);
+                }
+                first_class.get_or_insert(name);
+                break;
+            } else if !MODIFIERS.contains(&token) {
+                break;
+            }
+        }
+    }
+
+    first_class
+}
+
+/// Keep only the code outside of any braces, with the comments and the literals removed. Each
+/// top-level statement and block starts on a new line, and the line breaks are kept.
+fn top_level_code(source_code: &str) -> String {
+    let mut code = String::new();
+    let mut depth = 0usize;
+    let mut chars = source_code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                while matches!(chars.peek(), Some(&c) if c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        code.push(c);
+                    }
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            '"' | '\'' => {
+                while let Some(next) = chars.next() {
+                    if next == '\\' {
+                        chars.next();
+                    } else if next == c || next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '{' => {
+                if depth == 0 {
+                    code.push('\n');
+                }
+                depth += 1;
+            }
+            '}' => {
+                depth = depth.saturating_sub(1);
+                code.push('\n');
+            }
+            ';' if depth == 0 => code.push('\n'),
+            '\n' => code.push(c),
+            _ if depth == 0 => code.push(c),
+            _ => {}
+        }
+    }
+
+    code
+}
+
+fn new_template_engine() -> Handlebars<'static> {
+    let mut template_engine = Handlebars::new();
+    // The tokens are passed as arguments and filenames, not embedded in HTML.
+    template_engine.register_escape_fn(handlebars::no_escape);
+    template_engine
+}
+
+fn render_token(
+    template_engine: &Handlebars,
+    token: &str,
+    context: &TemplateContext,
+) -> Result<String> {
+    template_engine
+        .render_template(token, context)
+        .map_err(|err| Error::Template(format!("Failed to render {:?}: {}", token, err)))
+}
+
+/// Render the tokens of a command. A token consisting only of `{{extra_files}}` is expanded to
+/// a token for each extra file.
+fn render_command(command: &[String], context: &TemplateContext) -> Result<Vec<String>> {
+    let template_engine = new_template_engine();
+
+    let mut tokens = vec![];
+    for token in command.iter() {
+        if token.trim() == "{{extra_files}}" {
+            tokens.extend(context.extra_files.iter().cloned());
+            continue;
+        }
+        tokens.push(render_token(&template_engine, token, context)?);
+    }
+
+    Ok(tokens)
+}
+
+impl Language {
+    /// The filenames of the source and the executable inside the sandbox. The filenames may be
+    /// derived from the main class of the source, e.g. `{{main_class}}.java` for Java, whose
+    /// public class must be named after the file.
+    pub fn filenames(&self, source_code: &str) -> Result<(String, String)> {
+        let template_engine = new_template_engine();
+        let context = TemplateContext {
+            main_class: detect_main_class(source_code)
+                .unwrap_or_else(|| DEFAULT_MAIN_CLASS.to_string()),
+            ..TemplateContext::default()
+        };
+
+        Ok((
+            render_token(&template_engine, &self.source_filename, &context)?,
+            render_token(&template_engine, &self.executable_filename, &context)?,
+        ))
+    }
+
+    pub fn compile(&self, context: &TemplateContext) -> Result<Vec<String>> {
+        render_command(&self.compile_command, context)
+    }
+
+    pub fn execute(&self, context: &TemplateContext) -> Result<Vec<String>> {
+        render_command(&self.execute_command, context)
+    }
+
+    /// The stack size (in KB) of programs in the language, given their memory limit.
+    pub fn stack_size(&self, memory_limit: i64) -> i64 {
+        match self.runtime.stack {
+            Some(Limit::Value(stack)) => stack as i64,
+            _ => memory_limit,
+        }
+    }
+}
+
+/// The variables passed to the templates of both the compile and the execute commands.
+const COMMON_VARIABLES: &[&str] = &[
+    "source_basename",
+    "main_class",
+    "memory_limit",
+    "time_limit",
+    "stack_size",
+    "extra_files",
+];
+/// The variables passed to the templates of the compile command.
+const COMPILE_VARIABLES: &[&str] = &["source", "destination"];
+/// The variables passed to the templates of the execute command.
+const EXECUTE_VARIABLES: &[&str] = &["executable"];
+/// The variables passed to the templates of the source and executable filenames.
+const FILENAME_VARIABLES: &[&str] = &["main_class"];
+
+/// Find the names of the variables referred to in a Handlebars template.
+fn template_variables(template: &str) -> Vec<&str> {
+    let mut variables = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => break,
+        };
+        let expression = after[..end].trim().trim_start_matches('{');
+        if let Some(name) = expression.split_whitespace().next() {
+            // Helpers such as `#if`, comments and the variables of blocks are not variables.
+            if !name.starts_with(&['#', '/', '!', '>', '@'][..]) && name != "else" && name != "this"
+            {
+                variables.push(name.split('.').next().unwrap_or(name));
+            }
+        }
+        rest = &after[end + 2..];
+    }
+    variables
+}
+
+/// Whether a program can be found, either as a path or in `PATH`.
+fn program_exists(program: &str) -> bool {
+    if program.contains('/') {
+        return std::path::Path::new(program).exists();
+    }
+    match std::env::var_os("PATH") {
+        Some(paths) => std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()),
+        None => false,
+    }
+}
+
+/// Check the templates of a command, returning the problems found.
+fn check_command(command: &[String], variables: &[&str], kind: &str) -> Vec<String> {
+    let mut problems = vec![];
+
+    if command.is_empty() {
+        problems.push(format!("The {} command is empty.", kind));
+        return problems;
+    }
+
+    let mut template_engine = Handlebars::new();
+    for token in command.iter() {
+        if let Err(err) = template_engine.register_template_string("token", token) {
+            problems.push(format!(
+                "Invalid template {:?} in the {} command: {}",
+                token, kind, err
+            ));
+            continue;
+        }
+        for variable in template_variables(token) {
+            if !variables.contains(&variable) {
+                problems.push(format!(
+                    "Unknown variable {} in the {} command. Expected one of: {}.",
+                    variable,
+                    kind,
+                    variables.join(", ")
+                ));
+            }
+        }
+    }
+
+    problems
 }
 
 impl Language {
-    pub fn compile(&self, source: &str, destination: &str) -> Vec<String> {
-        let template_engine = Handlebars::new();
-        let mut map = BTreeMap::new();
-        map.insert("source", source);
-        map.insert("destination", destination);
+    /// Find the programs of the commands which cannot be found on this host, returning the
+    /// problems found. This is not a problem of the definition, since the language may not be
+    /// installed on every host.
+    pub fn missing_programs(&self) -> Vec<String> {
+        let mut commands = vec![
+            (&self.compile_command, "compile"),
+            (&self.execute_command, "execute"),
+        ];
+        if let Some(version_command) = &self.version_command {
+            commands.push((version_command, "version"));
+        }
 
-        return self
+        let mut problems = vec![];
+        for (command, kind) in commands {
+            // The program itself can only be checked if it is not a template.
+            match command.first() {
+                Some(program) if !program.contains("{{") && !program_exists(program) => {
+                    problems.push(format!(
+                        "The program {} of the {} command is not found.",
+                        program, kind
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        problems
+    }
+
+    /// Check the definition of the language, returning the problems found.
+    pub fn check(&self) -> Vec<String> {
+        let mut problems = vec![];
+
+        problems.extend(check_command(
+            &self.compile_command,
+            &[COMPILE_VARIABLES, COMMON_VARIABLES].concat(),
+            "compile",
+        ));
+        problems.extend(check_command(
+            &self.execute_command,
+            &[EXECUTE_VARIABLES, COMMON_VARIABLES].concat(),
+            "execute",
+        ));
+        if let Some(version_command) = &self.version_command {
+            problems.extend(check_command(version_command, &[], "version"));
+        }
+        for filename in [&self.source_filename, &self.executable_filename].iter() {
+            for variable in template_variables(filename) {
+                if !FILENAME_VARIABLES.contains(&variable) {
+                    problems.push(format!(
+                        "Unknown variable {} in the filename {}. Expected one of: {}.",
+                        variable,
+                        filename,
+                        FILENAME_VARIABLES.join(", ")
+                    ));
+                }
+            }
+        }
+        if !self
             .compile_command
             .iter()
-            .map(|s| template_engine.render_template(s, &map).unwrap())
-            .collect();
+            .any(|token| template_variables(token).contains(&"source"))
+        {
+            problems.push("The compile command does not refer to {{source}}.".to_string());
+        }
+        if let Err(message) = self.runtime.validate() {
+            problems.push(message);
+        }
+
+        problems
     }
+}
 
-    pub fn execute(&self, executable: &str) -> Vec<String> {
-        let template_engine = Handlebars::new();
-        let mut map = BTreeMap::new();
-        map.insert("executable", executable);
+fn key(name: &str) -> Value {
+    Value::String(name.to_string())
+}
 
-        return self
-            .execute_command
+/// Read the entries of a languages definition file. An entry `include: <path>` is replaced by
+/// the entries of the file, relative to the including file. The files being read are kept in
+/// `including` for detecting recursive includes.
+fn read_entries(path: &Path, including: &mut Vec<PathBuf>) -> Result<Vec<Mapping>> {
+    let canonical = path.canonicalize()?;
+    if including.contains(&canonical) {
+        return Err(Error::Option(format!(
+            "The languages definition {} is included recursively.",
+            path.display()
+        )));
+    }
+    including.push(canonical);
+
+    let file = std::fs::File::open(path)?;
+    let values: Vec<Value> = serde_yaml::from_reader(file)?;
+    let mut entries = vec![];
+    for value in values {
+        let entry = match value {
+            Value::Mapping(entry) => entry,
+            _ => {
+                return Err(Error::Option(format!(
+                    "An entry of the languages definition {} is not a mapping.",
+                    path.display()
+                )))
+            }
+        };
+        match entry.get(&key("include")) {
+            Some(Value::String(include)) if entry.len() == 1 => {
+                let base = path.parent().unwrap_or_else(|| Path::new(""));
+                entries.extend(read_entries(&base.join(include), including)?);
+            }
+            Some(_) => {
+                return Err(Error::Option(format!(
+                    "An include in the languages definition {} must be the only key of its entry.",
+                    path.display()
+                )))
+            }
+            None => entries.push(entry),
+        }
+    }
+
+    including.pop();
+    Ok(entries)
+}
+
+/// Merge the overriding mapping into the base mapping. Nested mappings, e.g. `runtime`, are
+/// merged recursively, and other values are replaced.
+fn merge_mapping(base: &mut Mapping, overriding: &Mapping) {
+    for (name, value) in overriding.iter() {
+        match (base.get_mut(name), value) {
+            (Some(Value::Mapping(base)), Value::Mapping(overriding)) => {
+                merge_mapping(base, overriding)
+            }
+            _ => {
+                base.insert(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Resolve the `extends` key of the entries, so that each entry inherits the fields of the entry
+/// with the given code, overriding some of them.
+fn resolve_extends(entries: Vec<Mapping>) -> Result<Vec<Mapping>> {
+    let code_of = |entry: &Mapping| match entry.get(&key("code")) {
+        Some(Value::String(code)) => Some(code.clone()),
+        _ => None,
+    };
+    let find = |code: &str| {
+        entries
             .iter()
-            .map(|s| template_engine.render_template(s, &map).unwrap())
+            .find(|entry| code_of(entry).as_deref() == Some(code))
+    };
+
+    let mut resolved = vec![];
+    for entry in entries.iter() {
+        // Follow the chain of parents, from the entry to the root.
+        let mut chain = vec![entry];
+        while let Some(parent) = chain.last().unwrap().get(&key("extends")) {
+            let parent = match parent {
+                Value::String(parent) => parent,
+                _ => return Err(Error::Option("The extends key must be a code.".to_string())),
+            };
+            let parent_entry = find(parent).ok_or_else(|| {
+                Error::Option(format!(
+                    "The language {} to be extended is not defined.",
+                    parent
+                ))
+            })?;
+            if chain.iter().any(|entry| std::ptr::eq(*entry, parent_entry)) {
+                return Err(Error::Option(format!(
+                    "The language {} extends itself.",
+                    parent
+                )));
+            }
+            chain.push(parent_entry);
+        }
+
+        let mut merged = Mapping::new();
+        for ancestor in chain.iter().rev() {
+            merge_mapping(&mut merged, ancestor);
+        }
+        merged.remove(&key("extends"));
+        resolved.push(merged);
+    }
+
+    Ok(resolved)
+}
+
+/// The validated definitions of the languages, loaded once and shared by all judge threads.
+#[derive(Clone)]
+pub struct LanguageRegistry {
+    languages: Vec<Language>,
+}
+
+impl LanguageRegistry {
+    /// Create the registry, checking every language and rejecting duplicated codes. All the
+    /// problems found are reported together.
+    pub fn new(languages: Vec<Language>) -> Result<LanguageRegistry> {
+        let mut problems = vec![];
+
+        for (i, language) in languages.iter().enumerate() {
+            if languages[..i].iter().any(|l| l.code == language.code) {
+                problems.push(format!(
+                    "{}: The language code is duplicated.",
+                    language.code
+                ));
+            }
+            for problem in language.check() {
+                problems.push(format!("{}: {}", language.code, problem));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(LanguageRegistry { languages })
+        } else {
+            Err(Error::Option(format!(
+                "The languages definition is invalid:\n  {}",
+                problems.join("\n  ")
+            )))
+        }
+    }
+
+    /// Read and check the definitions of the languages from a YAML file.
+    pub fn load(languages_definition: &str) -> Result<LanguageRegistry> {
+        log::debug!(
+            "Reading languages definition from {}...",
+            &languages_definition
+        );
+
+        let entries = read_entries(Path::new(languages_definition), &mut vec![])?;
+        let languages = resolve_extends(entries)?
+            .into_iter()
+            .map(|entry| serde_yaml::from_value(Value::Mapping(entry)))
+            .collect::<std::result::Result<Vec<Language>, _>>()?;
+
+        // Languages not installed on this host only fail the submissions in them.
+        for language in languages.iter() {
+            for problem in language.missing_programs() {
+                log::warn!("{}: {}", language.code, problem);
+            }
+        }
+
+        LanguageRegistry::new(languages)
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Language> {
+        self.languages.iter().find(|language| language.code == code)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Language> {
+        self.languages.iter()
+    }
+
+    /// Infer the language of a source file from its extension. It is an error if no language or
+    /// more than one language has the extension.
+    pub fn infer(&self, source: &str) -> Result<&Language> {
+        let extension = std::path::Path::new(source)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let candidates: Vec<&Language> = self
+            .languages
+            .iter()
+            .filter(|language| {
+                language
+                    .extensions
+                    .iter()
+                    .any(|e| e.trim_start_matches('.').to_lowercase() == extension)
+            })
             .collect();
+
+        match candidates.len() {
+            1 => Ok(candidates[0]),
+            0 => Err(Error::Option(format!(
+                "No language has the extension of {}.",
+                source
+            ))),
+            _ => Err(Error::Option(format!(
+                "The language of {} is ambiguous. Candidates: {}.",
+                source,
+                candidates
+                    .iter()
+                    .map(|language| &language.code[..])
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_settings() {
+        let runtime: RuntimeSettings = serde_yaml::from_str(
+            "stack: unlimited\nprocesses: 16\nenv:\n  JAVA_HOME: /usr/lib/jvm\ndirs:\n  - inside: /usr/lib/jvm\n    options: [noexec]\n",
+        )
+        .unwrap();
+        assert_eq!(runtime.stack, Some(Limit::Unlimited));
+        assert_eq!(runtime.processes, Some(Limit::Value(16)));
+        assert!(runtime.validate().is_ok());
+
+        assert!(serde_yaml::from_str::<RuntimeSettings>("stack: infinite\n").is_err());
+        assert!(serde_yaml::from_str::<RuntimeSettings>("open_files: 0\n").is_err());
+
+        let runtime: RuntimeSettings =
+            serde_yaml::from_str("dirs:\n  - inside: /tmp\n    options: [exec]\n").unwrap();
+        assert!(runtime.validate().is_err());
+    }
+
+    fn language(compile_command: &[&str], execute_command: &[&str]) -> Language {
+        Language {
+            source_filename: "source.py".to_string(),
+            executable_filename: "program.py".to_string(),
+            code: "python3".to_string(),
+            compile_command: compile_command.iter().map(|s| s.to_string()).collect(),
+            execute_command: execute_command.iter().map(|s| s.to_string()).collect(),
+            compile_flags: None,
+            execute_flags: None,
+            memory_baseline: 0,
+            runtime: RuntimeSettings::default(),
+            version_command: None,
+            extensions: vec![],
+            hello_world: None,
+        }
+    }
+
+    #[test]
+    fn test_language_registry() {
+        let valid = language(
+            &["/bin/cp", "{{source}}", "{{destination}}"],
+            &["{{executable}}"],
+        );
+        assert!(LanguageRegistry::new(vec![valid.clone()]).is_ok());
+        assert!(LanguageRegistry::new(vec![valid.clone(), valid]).is_err());
+
+        let invalid = language(
+            &["/bin/cp", "{{src}}", "{{destination}}"],
+            &["{{executable}}"],
+        );
+        let problems = invalid.check();
+        assert!(problems.iter().any(|p| p.contains("Unknown variable src")));
+        assert!(problems
+            .iter()
+            .any(|p| p.contains("does not refer to {{source}}")));
+
+        let invalid = language(
+            &["/nonexistent/compiler", "{{source}}"],
+            &["{{executable}}"],
+        );
+        assert!(invalid.check().is_empty());
+        assert!(invalid
+            .missing_programs()
+            .iter()
+            .any(|p| p.contains("not found")));
+        assert!(LanguageRegistry::new(vec![invalid]).is_ok());
+    }
+
+    #[test]
+    fn test_template_context() {
+        let source_code = "import java.util.*;\n\npublic final class Solution {\n}\n";
+        assert_eq!(detect_main_class(source_code), Some("Solution".to_string()));
+        assert_eq!(
+            detect_main_class("class Helper {}\npublic class Solution {}"),
+            Some("Solution".to_string())
+        );
+        assert_eq!(detect_main_class("class Foo {}"), Some("Foo".to_string()));
+        assert_eq!(detect_main_class("print(1)"), None);
+
+        // Nested classes, comments and literals are not declarations of the main class.
+        let nested = "// class Comment\nclass Main {\n    public static class Reader {}\n}\n";
+        assert_eq!(detect_main_class(nested), Some("Main".to_string()));
+        assert_eq!(
+            detect_main_class("class Main { public static class Reader { String s = \"}\"; } }"),
+            Some("Main".to_string())
+        );
+        assert_eq!(
+            detect_main_class("/* public class A {} */ package a; public class B {}"),
+            Some("B".to_string())
+        );
+
+        let mut java = language(
+            &[
+                "/bin/cp",
+                "{{source}}",
+                "{{extra_files}}",
+                "{{destination}}",
+            ],
+            &["/usr/bin/java", "-Xmx{{memory_limit}}k", "{{main_class}}"],
+        );
+        let context = TemplateContext {
+            memory_limit: 262144,
+            extra_files: vec!["grader.java".to_string(), "a&b.txt".to_string()],
+            ..TemplateContext::new("Main.java", "Main.class", source_code)
+        };
+        assert_eq!(
+            java.compile(&context).unwrap(),
+            vec![
+                "/bin/cp",
+                "Main.java",
+                "grader.java",
+                "a&b.txt",
+                "Main.class"
+            ]
+        );
+        assert_eq!(
+            java.execute(&context).unwrap(),
+            vec!["/usr/bin/java", "-Xmx262144k", "Solution"]
+        );
+
+        java.source_filename = "{{main_class}}.java".to_string();
+        java.executable_filename = "{{main_class}}.class".to_string();
+        assert_eq!(
+            java.filenames(source_code).unwrap(),
+            ("Solution.java".to_string(), "Solution.class".to_string())
+        );
+        assert_eq!(
+            java.filenames("").unwrap(),
+            ("Main.java".to_string(), "Main.class".to_string())
+        );
+        assert!(!java.check().iter().any(|p| p.contains("filename")));
+    }
+
+    #[test]
+    fn test_infer_language() {
+        let mut cpp14 = language(&["/bin/cp", "{{source}}"], &["{{executable}}"]);
+        cpp14.code = "cpp14".to_string();
+        cpp14.extensions = vec!["cpp".to_string(), ".cc".to_string()];
+        let mut cpp17 = cpp14.clone();
+        cpp17.code = "cpp17".to_string();
+        cpp17.extensions = vec!["cpp".to_string()];
+        let mut python3 = language(&["/bin/cp", "{{source}}"], &["{{executable}}"]);
+        python3.extensions = vec!["py".to_string()];
+
+        let registry = LanguageRegistry::new(vec![cpp14, cpp17, python3]).unwrap();
+        assert_eq!(registry.infer("sol/Main.CC").unwrap().code, "cpp14");
+        assert_eq!(registry.infer("a.py").unwrap().code, "python3");
+        assert!(registry.infer("a.rs").is_err());
+        assert!(registry.infer("Makefile").is_err());
+        match registry.infer("a.cpp") {
+            Err(err) => assert!(err.to_string().contains("Candidates: cpp14, cpp17.")),
+            Ok(_) => panic!("The language should be ambiguous."),
+        }
+    }
+
+    #[test]
+    fn test_resolve_extends() {
+        let entries: Vec<Mapping> = serde_yaml::from_str(
+            "
+- code: cpp17
+  compile_command: [/usr/bin/g++, --std=c++17, '{{source}}']
+  runtime:
+    stack: unlimited
+    processes: 1
+- code: cpp20
+  extends: cpp17
+  compile_command: [/usr/bin/g++, --std=c++20, '{{source}}']
+- code: cpp20-asan
+  extends: cpp20
+  runtime:
+    processes: unlimited
+",
+        )
+        .unwrap();
+        let resolved = resolve_extends(entries).unwrap();
+
+        let asan = &resolved[2];
+        assert_eq!(asan.get(&key("code")), Some(&key("cpp20-asan")));
+        assert_eq!(asan.get(&key("extends")), None);
+        assert_eq!(
+            asan.get(&key("compile_command")),
+            resolved[1].get(&key("compile_command"))
+        );
+        let runtime: RuntimeSettings =
+            serde_yaml::from_value(asan.get(&key("runtime")).unwrap().clone()).unwrap();
+        assert_eq!(runtime.stack, Some(Limit::Unlimited));
+        assert_eq!(runtime.processes, Some(Limit::Unlimited));
+
+        let cyclic: Vec<Mapping> =
+            serde_yaml::from_str("[{code: a, extends: b}, {code: b, extends: a}]").unwrap();
+        assert!(resolve_extends(cyclic).is_err());
+        let missing: Vec<Mapping> = serde_yaml::from_str("[{code: a, extends: b}]").unwrap();
+        assert!(resolve_extends(missing).is_err());
     }
 }
@@ -0,0 +1,18 @@
+//! MiniJudge-Rust
+//! A miniature judge written in Rust. The judge can be embedded with the `Judge` builder, or used
+//! from the command line.
+
+pub mod cache;
+pub mod communications;
+pub mod error;
+pub mod format;
+mod html;
+pub mod judge;
+pub mod languages;
+pub mod observer;
+pub mod problem;
+pub mod runner;
+pub mod sandbox;
+mod state;
+
+pub use runner::{Judge, JudgeBuilder, Submission};
@@ -1,29 +1,48 @@
+mod batch;
+mod calibrate;
+mod check;
 mod cli;
-mod communications;
 mod debug;
-mod error;
-mod judge;
-mod languages;
 mod precheck;
-mod sandbox;
-mod state;
+mod verify;
 
 use clap::derive::Clap;
 use cli::*;
-use judge::TestcaseOutput;
-use languages::Language;
-use sandbox::Sandbox;
-use state::AppState;
-use std::borrow::Borrow;
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
-use std::thread;
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+use minijudge_rust::cache::CompileCache;
+use minijudge_rust::error::Error;
+use minijudge_rust::judge;
+use minijudge_rust::languages::{Language, LanguageRegistry};
+use minijudge_rust::observer::{JsonLinesObserver, ZmqObserver};
+use minijudge_rust::problem::{self, Problem};
+use minijudge_rust::sandbox::{self, SandboxPool};
+use minijudge_rust::{Judge, JudgeBuilder, Submission};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicI32, Ordering};
+
+/// The submission is judged, with a verdict other than SE.
+const EXIT_JUDGED: i32 = 0;
+/// The judge failed by itself, and the verdict is SE.
+const EXIT_SYSTEM_ERROR: i32 = 1;
+/// The options, the metadata or the languages definition are invalid. This is also the exit code
+/// of clap when the command line arguments cannot be parsed.
+const EXIT_INVALID: i32 = 2;
+/// The verification or the calibration of the problem failed.
+const EXIT_FAILED: i32 = 3;
+
+/// The write end of the pipe through which the signal handler wakes up the cleanup thread.
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+fn main() {
     let opts: Opts = Opts::parse();
 
-    // Derive log level from CLI options and construct logger.
+    // Derive log level from CLI options and construct logger. The log is written to standard error
+    // if the progress is written to standard output, so that the progress can be parsed.
     let log_level = cli::calc_log_level(opts.verbosity, opts.quiet);
+    let log_output: fern::Output = if opts.progress_to_stdout() {
+        std::io::stderr().into()
+    } else {
+        std::io::stdout().into()
+    };
     fern::Dispatch::new()
         .format(|out, message, record| {
             out.finish(format_args!(
@@ -36,30 +55,112 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         })
         .level(log::LevelFilter::Error)
         .level_for("minijudge_rust", log_level)
-        .chain(std::io::stdout())
+        .chain(log_output)
         // .chain(fern::log_file("output.log")?)
         .apply()
         .unwrap();
 
     debug::debug_opts(&opts);
 
-    let socket: Option<Arc<Mutex<zmq::Socket>>> = if let Some(socket) = &opts.socket {
-        let context = zmq::Context::new();
-        let responder = context.socket(zmq::PUB).unwrap();
-        responder
-            .set_sndhwm(1_100_100)
-            .expect("Failed setting hwm.");
-        responder.bind(socket).expect("Failed binding publisher.");
-        Some(Arc::new(Mutex::new(responder)))
-    } else {
-        None
+    // The sandboxes are cleaned up when the pool is dropped, also when the judge panics, but not
+    // when the process is killed by a signal.
+    if let Err(err) = handle_signals() {
+        log::warn!("Failed to handle signals: {}", err);
+    }
+
+    let err = match panic::catch_unwind(AssertUnwindSafe(|| run(&opts))) {
+        Ok(Ok(exit_code)) => std::process::exit(exit_code),
+        Ok(Err(err)) => err,
+        Err(payload) => Error::from_panic(payload.as_ref()),
     };
+    log::error!("{}", err);
+
+    // The frontend expects a verdict whenever a submission is judged, so a system error is
+    // reported even if the judge fails before judging.
+    if opts.command.is_none() {
+        let testcase_count = opts
+            .metadata
+            .as_deref()
+            .and_then(|metadata| problem::read_metadata(metadata).ok())
+            .map(|metadata| metadata.testcases.len())
+            .unwrap_or(0);
+        let judge_output = judge::system_error_output(testcase_count, &err);
+        if let Err(err) = flush_verdict(&opts, &judge_output, None) {
+            log::error!("Failed to output the verdict: {}", err);
+        }
+    }
+
+    std::process::exit(exit_code(&err));
+}
+
+/// Clean up the sandboxes and exit when the process receives SIGINT or SIGTERM. The signal handler
+/// only writes the signal to a pipe, and the cleanup runs in a thread reading from the pipe, since
+/// little is allowed in a signal handler.
+fn handle_signals() -> Result<(), Error> {
+    extern "C" fn on_signal(signal: libc::c_int) {
+        let byte = signal as u8;
+        unsafe {
+            libc::write(
+                SIGNAL_PIPE.load(Ordering::SeqCst),
+                &byte as *const u8 as *const libc::c_void,
+                1,
+            );
+        }
+    }
+
+    let mut fds = [0 as libc::c_int; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    SIGNAL_PIPE.store(fds[1], Ordering::SeqCst);
+
+    std::thread::spawn(move || {
+        let mut byte = 0u8;
+        let read = unsafe { libc::read(fds[0], &mut byte as *mut u8 as *mut libc::c_void, 1) };
+        if read == 1 {
+            log::error!("Received signal {}. Cleaning up the sandboxes.", byte);
+            sandbox::cleanup_active_sandboxes();
+            std::process::exit(128 + byte as i32);
+        }
+    });
+
+    for signal in [libc::SIGINT, libc::SIGTERM].iter() {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        if unsafe { libc::signal(*signal, handler) } == libc::SIG_ERR {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+
+    Ok(())
+}
 
-    let metadata = match read_metadata(&opts.metadata) {
+/// The exit code of the process when it fails with the error.
+fn exit_code(err: &Error) -> i32 {
+    match err {
+        Error::Option(_) | Error::Parse(_) | Error::Observer(_) | Error::Template(_) => {
+            EXIT_INVALID
+        }
+        Error::Calibration(_) => EXIT_FAILED,
+        _ => EXIT_SYSTEM_ERROR,
+    }
+}
+
+/// Run the judge or the subcommand, returning the exit code of the process.
+fn run(opts: &Opts) -> Result<i32, Error> {
+    if let Some(Command::Languages(languages_opts)) = &opts.command {
+        return run_languages(opts, languages_opts);
+    }
+
+    // Check that the problem is given before reading the metadata.
+    if opts.metadata.is_none() {
+        return Err(Error::Option(
+            "The metadata file must be specified.".to_string(),
+        ));
+    }
+    let metadata = match problem::read_metadata(opts.metadata_path()) {
         Ok(metadata) => metadata,
         Err(err) => {
             log::error!("Failed to read metadata.");
-            // TODO output SE verdict.
             return Err(err);
         }
     };
@@ -74,441 +175,179 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     // Check that the files referred to in opts and metadata all exist.
-    if let Err(err) = precheck::precheck_opts(&opts) {
+    if let Err(err) = precheck::precheck_opts(opts) {
         log::error!("Error when checking command line options: {:?}", err);
         return Err(err);
     }
 
-    if let Err(err) = precheck::precheck_metadata(&opts, &metadata) {
+    if let Err(err) = precheck::precheck_metadata(opts, &metadata) {
         log::error!("Error when checking metadata: {:?}", err);
         return Err(err);
     }
 
     log::info!("Options and metadata are checked.");
 
-    // Generate a list of testcases for judge to consume.
-    let testcases_stack: Arc<Mutex<Vec<Testcase>>> = Arc::new(Mutex::new(
-        metadata
-            .testcases
-            .clone()
-            .into_iter()
-            .rev()
-            .collect::<Vec<Testcase>>(),
-    ));
-
-    let judge_output: Arc<Mutex<judge::JudgeOutput>> = Arc::new(Mutex::new(judge::JudgeOutput {
-        verdict: judge::VERDICT_WJ.to_string(),
-        time: 0.0,
-        memory: 0,
-        compile_message: "".into(),
-        testcases: vec![
-            judge::TestcaseOutput {
-                verdict: judge::VERDICT_WJ.to_string(),
-                time: 0.0,
-                memory: 0,
-                checker_output: "".to_string(),
-                sandbox_output: "".to_string(),
-            };
-            metadata.testcases.len()
-        ],
-    }));
-
-    let sandbox_count: i32 = opts.sandboxes;
-    assert!(sandbox_count >= 1);
-
-    let mut sandboxes = Vec::new();
-
-    for i in 0..sandbox_count {
-        sandboxes.push(Sandbox::create(i)?);
-    }
+    let languages = LanguageRegistry::load(&opts.languages_definition)?;
 
-    let sandbox_primary = &sandboxes[0];
-
-    let source_language = cli::detect_language(&opts.language, &opts.languages_definition).unwrap();
-    let source_file = &source_language.source_filename;
-    let executable_file = &source_language.executable_filename;
-
-    sandbox_primary.copy_into(&opts.source, &source_file)?;
-    let result = match compile_source(
-        sandbox_primary,
-        &source_language,
-        &metadata,
-        &source_file,
-        &executable_file,
-    ) {
-        Ok(output) => {
-            judge_output.lock().unwrap().compile_message =
-                String::from_utf8_lossy(&output.stderr).to_string();
-
-            if output.status.success() {
-                Ok(output)
-            } else {
-                // Compile error
-                Err(judge_definitions::verdicts::VERDICT_CE.to_string())
-            }
-        }
-        Err(err) => {
-            log::error!("Unexpected error: {:?}", err);
-            Err(judge_definitions::verdicts::VERDICT_SE.to_string())
-        }
+    let problem = Problem {
+        metadata,
+        testcases: opts.testcases.clone().unwrap_or_default(),
+        checker: opts.checker.clone().unwrap_or_default(),
+        checker_language: opts.checker_language.clone().unwrap_or_default(),
+        testlib: opts.testlib.clone().unwrap_or_default(),
     };
 
-    if let Err(verdict) = result {
-        let mut judge_output = judge_output.lock().unwrap();
-        judge_output.verdict = verdict.clone();
-        for i in 0..judge_output.testcases.len() {
-            judge_output.testcases[i] = judge::TestcaseOutput {
-                verdict: verdict.clone(),
-                ..judge_output.testcases[i].clone()
-            };
-        }
-        flush_verdict(&opts, &judge_output)?;
-        return Ok(());
-    }
+    let sandboxes = SandboxPool::create_with_offset(opts.sandboxes, opts.box_id_offset)?;
 
-    sandbox_primary.copy_into(&opts.testlib, "./testlib.h")?;
-    sandbox_primary.copy_into(&opts.checker, "./checker.cpp")?;
-
-    let result = match compile_checker(
-        sandbox_primary,
-        &detect_language(&opts.checker_language, &opts.languages_definition).unwrap(),
-        &metadata,
-        "checker.cpp",
-        "checker",
-    ) {
-        Ok(output) => {
-            if output.status.success() {
-                Ok(output)
-            } else {
-                log::error!(
-                    "Error when compiling checker:\n{}",
-                    String::from_utf8_lossy(&output.stderr)
-                );
-                Err(judge_definitions::verdicts::VERDICT_SE.to_string())
+    match &opts.command {
+        Some(Command::Calibrate(calibrate_opts)) => {
+            calibrate::calibrate(opts, calibrate_opts, &problem, languages, &sandboxes)?;
+            return Ok(EXIT_JUDGED);
+        }
+        Some(Command::Verify) => {
+            if !verify::verify(opts, &problem, languages, &sandboxes)? {
+                log::error!("Some solutions did not get their expected verdicts.");
+                return Ok(EXIT_FAILED);
             }
+            return Ok(EXIT_JUDGED);
         }
-        Err(err) => {
-            log::error!("Unexpected error: {:?}", err);
-            Err(judge_definitions::verdicts::VERDICT_SE.to_string())
+        Some(Command::Batch(batch_opts)) => {
+            let submissions = batch::read_manifest(&batch_opts.manifest, &languages)?;
+            let judge = configure_judge(opts, Judge::builder())?
+                .problem(problem)
+                .languages(languages)
+                .sandboxes(&sandboxes)
+                .verdict_sink(|problem, submission, judge_output| {
+                    batch::write_verdict(opts, batch_opts, problem, submission, judge_output)
+                })
+                .build()?;
+            if !batch::batch(opts, batch_opts, &judge, &submissions)? {
+                return Ok(EXIT_SYSTEM_ERROR);
+            }
+            return Ok(EXIT_JUDGED);
         }
-    };
+        Some(Command::Languages(_)) => unreachable!(),
+        None => {}
+    }
 
-    if let Err(verdict) = result {
-        let mut judge_output = judge_output.lock().unwrap();
-        judge_output.verdict = verdict.clone();
-        for i in 0..judge_output.testcases.len() {
-            judge_output.testcases[i] = judge::TestcaseOutput {
-                verdict: verdict.clone(),
-                ..judge_output.testcases[i].clone()
-            };
+    let source = opts.source.clone().unwrap();
+    let language = match &opts.language {
+        Some(language) => language.clone(),
+        None => {
+            let language = languages.infer(&source)?.code.clone();
+            log::info!("Inferred the language {} from the source file.", language);
+            language
         }
-        flush_verdict(&opts, &judge_output)?;
-        return Ok(());
-    }
+    };
 
-    // Copy the compiled binaries to other sandboxes.
-    for sb_sub in sandboxes.iter().skip(1) {
-        sandbox_primary.copy_across_sandbox(&sb_sub, "checker", "checker")?;
-        sandbox_primary.copy_across_sandbox(&sb_sub, &executable_file, &executable_file)?;
+    let judge = configure_judge(opts, Judge::builder())?
+        .problem(problem)
+        .submission(Submission {
+            id: None,
+            source,
+            language,
+        })
+        .languages(languages)
+        .sandboxes(&sandboxes)
+        .verdict_sink(|problem, _, judge_output| flush_verdict(opts, judge_output, Some(problem)))
+        .build()?;
+    let judge_output = judge.run()?;
+
+    if judge_output.verdict == judge::VERDICT_SE {
+        Ok(EXIT_SYSTEM_ERROR)
+    } else {
+        Ok(EXIT_JUDGED)
     }
+}
 
-    let state = Arc::new(AppState {
-        opts: opts.clone(),
-        metadata: metadata.clone(),
-        judge_output: judge_output.clone(),
-        testcases_stack: testcases_stack.clone(),
-        socket: match &socket {
-            Some(s) => Some(s.clone()),
-            None => None,
-        },
-    });
-
-    // Launch the judge threads.
-    let mut threads: Vec<thread::JoinHandle<()>> = Vec::new();
-    for (thread_id, thread_sb) in sandboxes.iter().enumerate() {
-        // Clone the variables to be passed into the thread.
-        let thread_id = thread_id;
-        let thread_sb = thread_sb.clone();
-        let state = state.clone();
-
-        let thread = thread::spawn(move || {
-            judge_thread(thread_id, thread_sb, state);
-        });
-
-        threads.push(thread);
+/// Attach the observers and the compile cache given in the options to the judge.
+fn configure_judge<'a>(
+    opts: &Opts,
+    mut judge: JudgeBuilder<'a>,
+) -> Result<JudgeBuilder<'a>, Error> {
+    if let Some(socket) = &opts.socket {
+        judge = judge.observer(ZmqObserver::bind(socket)?);
     }
-
-    // Wait for all threads to finish.
-    for thread in threads {
-        thread.join().unwrap();
+    if let Some(compile_cache) = &opts.compile_cache {
+        judge = judge.compile_cache(CompileCache::open(compile_cache)?);
     }
-
-    // Compute overall verdict, time and memory
-    let mut judge_output = judge_output.lock().unwrap();
-    judge::calc_overall_verdict(&mut judge_output);
-
-    flush_verdict(&opts, &*judge_output)?;
-
-    if let Some(socket) = socket {
-        let socket = socket.lock().unwrap();
-        let submission_json = serde_json::to_string(
-            communications::UpdateEvent::<judge::JudgeOutput> {
-                event_type: communications::EVENT_SUBMISSION.to_string(),
-                event: &*judge_output,
-            }
-            .borrow(),
-        )
-        .unwrap();
-        socket.send(&submission_json, 0).unwrap();
+    for progress in opts.progress.iter() {
+        judge = judge.observer(open_progress_observer(progress)?);
     }
 
-    Ok(())
+    Ok(judge)
 }
 
-fn flush_verdict(
-    opts: &Opts,
-    judge_output: &judge::JudgeOutput,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Generate the overall verdict.
-    let output = match &opts.verdict_format[..] {
-        "json" => serde_json::to_string(&*judge_output)?,
-        "yaml" => serde_yaml::to_string(&*judge_output)?,
-        _ => {
-            log::warn!("The verdict format is invalid. Defaulting to json.");
-            serde_json::to_string(&*judge_output)?
-        }
-    };
-
-    // Output the verdict to file if provided. Otherwise, output to standard input.
-    if let Some(verdict_file) = &opts.verdict {
-        std::fs::write(verdict_file, output)?;
-    } else {
-        println!("{}", output);
+/// Validate the languages definition and run the languages subcommand.
+fn run_languages(opts: &Opts, languages_opts: &LanguagesOpts) -> Result<i32, Error> {
+    if let Err(err) = precheck::precheck_opts(opts) {
+        log::error!("Error when checking command line options: {:?}", err);
+        return Err(err);
     }
 
-    Ok(())
-}
-
-fn judge_thread(thread_id: usize, thread_sb: sandbox::Sandbox, state: Arc<AppState>) {
-    log::debug!(
-        "Thread {} spawned. Sandbox at {}.",
-        thread_id,
-        &thread_sb.path.to_str().unwrap()
-    );
-
-    let AppState {
-        opts,
-        metadata,
-        judge_output,
-        testcases_stack,
-        ..
-    } = state.as_ref();
-
-    let source_language = cli::detect_language(&opts.language, &opts.languages_definition).unwrap();
-    let executable_file = &source_language.executable_filename;
-
-    loop {
-        let testcase: Option<Testcase> = state.testcases_stack.lock().unwrap().pop();
+    let languages = LanguageRegistry::load(&opts.languages_definition)?;
+    log::info!("The languages definition is valid.");
 
-        if testcase.is_none() {
-            log::debug!("Thread {} finds no test cases and terminates.", thread_id);
-            break;
+    match &languages_opts.command {
+        LanguagesCommand::Print => {
+            let languages: Vec<&Language> = languages.iter().collect();
+            print!("{}", serde_yaml::to_string(&languages)?);
+            Ok(EXIT_JUDGED)
         }
-
-        let Testcase { id, input, output } = testcase.unwrap();
-        let mut testcase_output: judge::TestcaseOutput =
-            judge_output.lock().unwrap().testcases[id].clone();
-
-        // Output and store the result of the testcase as needed.
-        let finalize_testcase = |testcase_output: &mut judge::TestcaseOutput| {
-            judge_output.lock().unwrap().testcases[id] = testcase_output.clone();
-
-            let total_testcases = judge_output.lock().unwrap().testcases.len();
-            let remaining_testcases = testcases_stack.lock().unwrap().len();
-            let processed_testcases = total_testcases - remaining_testcases;
-
-            log::debug!(
-                "Progress: {} processed / {} total",
-                processed_testcases,
-                total_testcases
-            );
-            log::debug!("Test {} processing completed by thread {}.", id, thread_id);
-            log::debug!(
-                "Test {}: Verdict = {}, Time = {}, Memory = {}",
-                id,
-                testcase_output.verdict,
-                testcase_output.time,
-                testcase_output.memory,
-            );
-
-            if let Some(socket) = &state.socket {
-                let socket = socket.lock().unwrap();
-                let testcase_json = serde_json::to_string(
-                    communications::UpdateEvent::<TestcaseOutput> {
-                        event_type: communications::EVENT_TESTCASE.to_string(),
-                        event: &testcase_output,
-                    }
-                    .borrow(),
-                )
-                .unwrap();
-                socket.send(&testcase_json, 0).unwrap();
+        LanguagesCommand::Check(check_opts) => {
+            if let Err(err) = precheck::precheck_env() {
+                log::error!("Error when checking environment: {:?}", err);
+                return Err(err);
             }
-        };
-
-        thread_sb
-            .copy_into(
-                PathBuf::from(&opts.testcases)
-                    .join(&input)
-                    .to_str()
-                    .unwrap(),
-                "in.txt",
-            )
-            .unwrap();
-
-        log::trace!("Test {} executing.", id);
-        let execute_result = thread_sb.run(
-            &source_language,
-            &sandbox::ExecuteConfig {
-                memory_limit: metadata.memory_limit,
-                time_limit: metadata.time_limit,
-                wall_time_limit: metadata.time_limit,
-                meta_file: Some("meta.txt"),
-                full_env: false,
-                unlimited_processes: false,
-                input_file: Some("in.txt"),
-                output_file: Some("out.txt"),
-                error_file: None,
-                ..sandbox::ExecuteConfig::default()
-            },
-            &executable_file,
-        );
-        log::trace!("Test {} executed.", id);
-
-        if execute_result.is_err() || thread_sb.read_file("meta.txt").is_err() {
-            testcase_output.verdict = judge_definitions::verdicts::VERDICT_SE.into();
-            finalize_testcase(&mut testcase_output);
-            continue;
-        }
-
-        let meta_file = thread_sb.read_file("meta.txt").unwrap();
-        let meta = judge::parse_meta(&meta_file);
-
-        if let Some(time) = &meta.time {
-            testcase_output.time = *time;
-        }
-        if let Some(memory) = &meta.memory {
-            testcase_output.memory = *memory;
-        }
-        if let Some(verdict) = &meta.verdict {
-            testcase_output.verdict = verdict.clone();
-        }
-
-        testcase_output.sandbox_output = meta_file.clone();
-
-        if meta.verdict.is_some() {
-            finalize_testcase(&mut testcase_output);
-            continue;
-        }
 
-        thread_sb
-            .copy_into(
-                PathBuf::from(&opts.testcases)
-                    .join(&output)
-                    .to_str()
-                    .unwrap(),
-                "ans.txt",
-            )
-            .unwrap();
-        let flags = vec!["checker", "in.txt", "out.txt", "ans.txt"];
-
-        log::trace!("Test {} checker executing.", id);
-        let checker_result = thread_sb.execute(
-            &sandbox::ExecuteConfig {
-                memory_limit: metadata.checker_memory_limit,
-                time_limit: metadata.checker_time_limit,
-                wall_time_limit: metadata.checker_time_limit,
-                error_file: Some("checker.txt"),
-                ..sandbox::ExecuteConfig::default()
-            },
-            &flags,
-        );
-
-        if checker_result.is_err() {
-            testcase_output.verdict = judge_definitions::verdicts::VERDICT_SE.into();
-            finalize_testcase(&mut testcase_output);
-            continue;
+            let sandboxes = SandboxPool::create_with_offset(opts.sandboxes, opts.box_id_offset)?;
+            if check::check(&languages, &sandboxes, check_opts.json)? {
+                Ok(EXIT_JUDGED)
+            } else {
+                log::error!("Some languages failed the check.");
+                Ok(EXIT_FAILED)
+            }
         }
+    }
+}
 
-        log::trace!("Test {} checker executed.", id);
-
-        let checker_output = thread_sb
-            .read_file("checker.txt")
-            .unwrap()
-            .trim()
-            .to_string();
-
-        let meta = judge::apply_checker_output(&meta, &checker_output);
-        testcase_output.checker_output = checker_output.clone();
-
-        if let Some(verdict) = meta.verdict {
-            testcase_output.verdict = verdict.clone();
+/// Open the observer writing updates to the destination given in `--progress`.
+fn open_progress_observer(destination: &str) -> Result<JsonLinesObserver, Error> {
+    match destination {
+        "stdout" => Ok(JsonLinesObserver::stdout()),
+        "stderr" => Ok(JsonLinesObserver::stderr()),
+        _ if destination.starts_with("unix:") => {
+            JsonLinesObserver::unix_socket(&destination["unix:".len()..])
         }
-
-        finalize_testcase(&mut testcase_output);
+        _ => JsonLinesObserver::file(destination),
     }
 }
 
-/// A helper function for compiling the source program.
-fn compile_source(
-    sb: &sandbox::Sandbox,
-    language: &Language,
-    metadata: &Metadata,
-    source: &str,
-    destination: &str,
-) -> Result<std::process::Output, Box<dyn std::error::Error>> {
-    sb.compile(
-        language,
-        &sandbox::ExecuteConfig {
-            memory_limit: metadata.compile_memory_limit,
-            time_limit: metadata.compile_time_limit,
-            wall_time_limit: metadata.compile_time_limit,
-            meta_file: None,
-            full_env: true,
-            unlimited_processes: true,
-            input_file: None,
-            output_file: None,
-            error_file: None,
-            ..sandbox::ExecuteConfig::default()
-        },
-        source,
-        destination,
-    )
-}
+fn flush_verdict(
+    opts: &Opts,
+    judge_output: &judge::JudgeOutput,
+    problem: Option<&Problem>,
+) -> Result<(), Error> {
+    // Output the verdict to the files if provided. Otherwise, output to standard output, with
+    // colors if it is a terminal. The verdict is only output to standard error if standard output
+    // holds the progress, which happens when the options are rejected.
+    if opts.verdict.is_empty() && opts.progress_to_stdout() {
+        let output = opts
+            .verdict_format_for(None)
+            .render(judge_output, problem, false)?;
+        eprintln!("{}", output.trim_end());
+    } else if opts.verdict.is_empty() {
+        let color = atty::is(atty::Stream::Stdout);
+        let output = opts
+            .verdict_format_for(None)
+            .render(judge_output, problem, color)?;
+        println!("{}", output.trim_end());
+    }
 
-/// A helper function for compiling the checker.
-fn compile_checker(
-    sb: &sandbox::Sandbox,
-    language: &Language,
-    metadata: &Metadata,
-    source: &str,
-    destination: &str,
-) -> Result<std::process::Output, Box<dyn std::error::Error>> {
-    sb.compile(
-        language,
-        &sandbox::ExecuteConfig {
-            memory_limit: metadata.compile_memory_limit,
-            time_limit: metadata.compile_time_limit,
-            wall_time_limit: metadata.compile_time_limit,
-            meta_file: None,
-            full_env: true,
-            unlimited_processes: true,
-            input_file: None,
-            output_file: None,
-            error_file: None,
-            additional_flags: Some(vec!["--full-env"]),
-            ..sandbox::ExecuteConfig::default()
-        },
-        source,
-        destination,
-    )
+    for verdict_file in opts.verdict.iter() {
+        let format = opts.verdict_format_for(Some(verdict_file));
+        std::fs::write(verdict_file, format.render(judge_output, problem, false)?)?;
+    }
+
+    Ok(())
 }
@@ -0,0 +1,230 @@
+use crate::communications::{self, *};
+use crate::error::Result;
+use crate::judge::{JudgeOutput, TestcaseOutput};
+use crate::runner::Submission;
+/// This module contains the observers notified of the progress of judging, e.g. to announce
+/// updates to other software.
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::sync::Mutex;
+
+/// An observer of the progress of judging a submission. All hooks do nothing by default.
+pub trait Observer: Send + Sync {
+    /// Called before a submission is judged. The events until the submission is judged are about
+    /// this submission.
+    fn on_submission_start(&self, _submission: &Submission) {}
+
+    /// Called before the source program is compiled.
+    fn on_compile_start(&self, _language: &str) {}
+
+    /// Called after the source program is compiled, successfully or not.
+    fn on_compile_end(&self, _success: bool, _compile_message: &str) {}
+
+    /// Called before a testcase is run.
+    fn on_testcase_start(&self, _id: usize) {}
+
+    /// Called when a testcase is judged.
+    fn on_testcase_end(&self, _id: usize, _testcase_output: &TestcaseOutput) {}
+
+    /// Called when the whole submission is judged.
+    fn on_submission_end(&self, _judge_output: &JudgeOutput) {}
+}
+
+/// A transport of serialized events.
+pub trait Transport: Send + Sync {
+    fn send(&self, message: &str) -> Result<()>;
+}
+
+/// An observer serializing every event to a line of JSON and sending it through a transport.
+pub struct JsonObserver<T: Transport> {
+    transport: T,
+    /// The ID of the submission being judged, attached to every event.
+    submission_id: Mutex<Option<String>>,
+}
+
+impl<T: Transport> JsonObserver<T> {
+    pub fn new(transport: T) -> JsonObserver<T> {
+        JsonObserver {
+            transport,
+            submission_id: Mutex::new(None),
+        }
+    }
+
+    fn send<E: serde::Serialize>(&self, event_type: &str, event: &E) {
+        let submission_id = self.submission_id.lock().unwrap().clone();
+        if let Err(err) = self.transport.send(&communications::to_json(
+            event_type,
+            submission_id.as_deref(),
+            event,
+        )) {
+            log::warn!("Failed to send {} event: {:?}", event_type, err);
+        }
+    }
+}
+
+impl<T: Transport> Observer for JsonObserver<T> {
+    fn on_submission_start(&self, submission: &Submission) {
+        *self.submission_id.lock().unwrap() = submission.id.clone();
+        // The start of a submission is only announced for submissions with IDs, so that the
+        // events of judging a single submission stay the same.
+        if submission.id.is_some() {
+            self.send(
+                EVENT_SUBMISSION_START,
+                &SubmissionStartEvent {
+                    language: &submission.language,
+                },
+            );
+        }
+    }
+
+    fn on_compile_start(&self, language: &str) {
+        self.send(EVENT_COMPILE_START, &CompileStartEvent { language });
+    }
+
+    fn on_compile_end(&self, success: bool, compile_message: &str) {
+        self.send(
+            EVENT_COMPILE_END,
+            &CompileEndEvent {
+                success,
+                compile_message,
+            },
+        );
+    }
+
+    fn on_testcase_start(&self, id: usize) {
+        self.send(EVENT_TESTCASE_START, &TestcaseStartEvent { id });
+    }
+
+    fn on_testcase_end(&self, id: usize, testcase_output: &TestcaseOutput) {
+        self.send(
+            EVENT_TESTCASE,
+            &TestcaseEvent {
+                id,
+                testcase_output,
+            },
+        );
+    }
+
+    fn on_submission_end(&self, judge_output: &JudgeOutput) {
+        self.send(EVENT_SUBMISSION, judge_output);
+    }
+}
+
+/// A transport publishing each event as a message on a ZeroMQ socket.
+pub struct ZmqTransport {
+    socket: Mutex<zmq::Socket>,
+}
+
+impl Transport for ZmqTransport {
+    fn send(&self, message: &str) -> Result<()> {
+        self.socket.lock().unwrap().send(message, 0)?;
+        Ok(())
+    }
+}
+
+/// A transport writing each event as a line to a stream.
+pub struct StreamTransport {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Transport for StreamTransport {
+    fn send(&self, message: &str) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        writeln!(writer, "{}", message)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// An observer publishing the events on a ZeroMQ socket.
+pub type ZmqObserver = JsonObserver<ZmqTransport>;
+
+/// An observer writing the events as newline-delimited JSON to a stream.
+pub type JsonLinesObserver = JsonObserver<StreamTransport>;
+
+impl ZmqObserver {
+    /// Create a publisher socket bound to the endpoint.
+    pub fn bind(endpoint: &str) -> Result<ZmqObserver> {
+        let context = zmq::Context::new();
+        let responder = context.socket(zmq::PUB)?;
+        responder.set_sndhwm(1_100_100)?;
+        responder.bind(endpoint)?;
+
+        Ok(JsonObserver::new(ZmqTransport {
+            socket: Mutex::new(responder),
+        }))
+    }
+}
+
+impl JsonLinesObserver {
+    pub fn from_writer<W: Write + Send + 'static>(writer: W) -> JsonLinesObserver {
+        JsonObserver::new(StreamTransport {
+            writer: Mutex::new(Box::new(writer)),
+        })
+    }
+
+    pub fn stdout() -> JsonLinesObserver {
+        JsonLinesObserver::from_writer(std::io::stdout())
+    }
+
+    pub fn stderr() -> JsonLinesObserver {
+        JsonLinesObserver::from_writer(std::io::stderr())
+    }
+
+    /// Create an observer writing to a file. The file is truncated if it exists.
+    pub fn file(path: &str) -> Result<JsonLinesObserver> {
+        Ok(JsonLinesObserver::from_writer(std::fs::File::create(path)?))
+    }
+
+    /// Create an observer writing to a Unix domain socket listened by other software.
+    pub fn unix_socket(path: &str) -> Result<JsonLinesObserver> {
+        Ok(JsonLinesObserver::from_writer(UnixStream::connect(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A writer keeping everything written in memory.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_lines_observer() {
+        let buffer = SharedBuffer::default();
+        let observer = JsonLinesObserver::from_writer(buffer.clone());
+
+        observer.on_compile_start("cpp17");
+        observer.on_testcase_start(2);
+        observer.on_submission_start(&Submission {
+            id: Some("s1".to_string()),
+            source: "main.py".to_string(),
+            language: "python3".to_string(),
+        });
+        observer.on_testcase_start(1);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                r#"{"event_type":"compile_start","event":{"language":"cpp17"}}"#,
+                r#"{"event_type":"testcase_start","event":{"id":2}}"#,
+                r#"{"event_type":"submission_start","submission_id":"s1","event":{"language":"python3"}}"#,
+                r#"{"event_type":"testcase_start","submission_id":"s1","event":{"id":1}}"#,
+            ]
+        );
+    }
+}
@@ -1,50 +1,88 @@
-use crate::cli::{Metadata, Opts};
-use crate::error::OptionError;
+use crate::cli::{Command, Opts};
+use minijudge_rust::error::{Error, Result};
+use minijudge_rust::problem::{solution_path, Metadata};
 /// This module contains files to ensure that the files to be used in the
 /// judging process specified by the user exists.
 use std::path::Path;
 
 /// A helper function for `precheck_opts` and `precheck_metadata` returning a
 /// well-formed error if the specified file does not exist.
-pub fn assert_exists(path_raw: &str, description: &str) -> Result<(), Box<OptionError>> {
+pub fn assert_exists(path_raw: &str, description: &str) -> Result<()> {
     let path = Path::new(path_raw);
 
     if path.exists() {
         Ok(())
     } else {
-        Err(Box::new(OptionError {
-            message: format!(
-                "The {} specified at {} does not exist.",
-                description, path_raw
-            ),
-        }))
+        Err(Error::Option(format!(
+            "The {} specified at {} does not exist.",
+            description, path_raw
+        )))
     }
 }
 
-pub fn precheck_env() -> Result<(), Box<OptionError>> {
+pub fn precheck_env() -> Result<()> {
     let output = std::process::Command::new("which")
         .arg("isolate")
-        .output()
-        .unwrap();
+        .output()?;
 
     if output.status.success() {
         Ok(())
     } else {
-        Err(Box::new(OptionError {
-            message: "The isolate sandbox is not found in path.".to_string(),
-        }))
+        Err(Error::Option(
+            "The isolate sandbox is not found in path.".to_string(),
+        ))
     }
 }
 
 /// Check that the files specified in the command line options exist.
-pub fn precheck_opts(opts: &Opts) -> Result<(), Box<OptionError>> {
-    assert_exists(&opts.metadata, "metadata file")?;
-    assert_exists(&opts.source, "source file")?;
-    assert_exists(&opts.checker, "checker file")?;
-    assert_exists(&opts.testcases, "testcases folder")?;
-    assert_exists(&opts.testlib, "testlib.h")?;
+pub fn precheck_opts(opts: &Opts) -> Result<()> {
     assert_exists(&opts.languages_definition, "languages definition file")?;
+    if opts.checks_languages() {
+        return Ok(());
+    }
+
+    let required = [
+        (&opts.metadata, "metadata file"),
+        (&opts.checker, "checker file"),
+        (&opts.testcases, "testcases folder"),
+        (&opts.testlib, "testlib.h"),
+    ];
+    for (path, description) in required.iter() {
+        match path {
+            Some(path) => assert_exists(path, description)?,
+            None => {
+                return Err(Error::Option(format!(
+                    "The {} must be specified.",
+                    description
+                )))
+            }
+        }
+    }
+    if opts.checker_language.is_none() {
+        return Err(Error::Option(
+            "The checker language must be specified.".to_string(),
+        ));
+    }
 
+    if opts.command.is_none() && opts.progress_to_stdout() && opts.verdict.is_empty() {
+        return Err(Error::Option(
+            "The verdict must be written to a file given in --verdict when the progress is written to standard output.".to_string(),
+        ));
+    }
+    if let Some(Command::Batch(batch_opts)) = &opts.command {
+        assert_exists(&batch_opts.manifest, "manifest file")?;
+    }
+    if opts.command.is_none() {
+        // The language is inferred from the extension of the source file if not specified.
+        match &opts.source {
+            Some(source) => assert_exists(source, "source file")?,
+            None => {
+                return Err(Error::Option(
+                    "The source file must be specified.".to_string(),
+                ))
+            }
+        }
+    }
     if let Some(interactor) = &opts.interactor {
         assert_exists(interactor, "interactor file")?;
     }
@@ -53,10 +91,20 @@ pub fn precheck_opts(opts: &Opts) -> Result<(), Box<OptionError>> {
 }
 
 /// Check that the test files
-pub fn precheck_metadata(opts: &Opts, metadata: &Metadata) -> Result<(), Box<OptionError>> {
+pub fn precheck_metadata(opts: &Opts, metadata: &Metadata) -> Result<()> {
+    if let Some(Command::Calibrate(_)) | Some(Command::Verify) = &opts.command {
+        for solution in metadata.solutions.iter() {
+            assert_exists(
+                &solution_path(opts.metadata_path(), solution),
+                &format!("solution {}", solution.source),
+            )?;
+        }
+    }
+
+    let testcases = opts.testcases.as_deref().unwrap_or_default();
     for (testcase_id, testcase) in metadata.testcases.iter().enumerate() {
-        let in_path = Path::new(&opts.testcases).join(&testcase.input);
-        let out_path = Path::new(&opts.testcases).join(&testcase.output);
+        let in_path = Path::new(testcases).join(&testcase.input);
+        let out_path = Path::new(testcases).join(&testcase.output);
         assert_exists(
             in_path.as_os_str().to_str().unwrap(),
             &format!("input file for test {}", testcase_id + 1),
@@ -66,6 +114,13 @@ pub fn precheck_metadata(opts: &Opts, metadata: &Metadata) -> Result<(), Box<Opt
             &format!("output file for test {}", testcase_id + 1),
         )?;
     }
+    for extra_file in metadata.extra_files.iter() {
+        let path = Path::new(testcases).join(extra_file);
+        assert_exists(
+            path.as_os_str().to_str().unwrap(),
+            &format!("extra file {}", extra_file),
+        )?;
+    }
 
     Ok(())
 }
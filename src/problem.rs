@@ -0,0 +1,206 @@
+use crate::error::Result;
+use crate::judge;
+use serde::{Deserialize, Serialize};
+/// This module contains the definition of a problem: its metadata and the files needed to judge
+/// submissions against it.
+use std::path::Path;
+
+/// This is the default ID for testcases when no ID is specified.
+fn default_id() -> usize {
+    0
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Testcase {
+    #[serde(default = "default_id")]
+    pub id: usize,
+    pub input: String,
+    pub output: String,
+    /// Whether the testcase is a sample shown to contestants, which decides the feedback given
+    /// for it.
+    #[serde(default)]
+    pub sample: bool,
+}
+
+/// The level of feedback given for a testcase, in increasing order of the details exposed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackLevel {
+    /// Only the verdict, the time and the memory usage.
+    Verdict,
+    /// The excerpts of the output and the error output of the program in addition.
+    Output,
+    /// The excerpts of the input and the expected answer in addition.
+    Full,
+}
+
+/// This is the default output limit (in KB) when no output limit is specified.
+fn default_output_limit() -> i64 {
+    65536
+}
+
+fn default_compile_message_bytes() -> u64 {
+    8192
+}
+
+fn default_excerpt_bytes() -> u64 {
+    judge::DEFAULT_EXCERPT_BYTES
+}
+
+fn default_sample_feedback() -> FeedbackLevel {
+    FeedbackLevel::Full
+}
+
+fn default_hidden_feedback() -> FeedbackLevel {
+    FeedbackLevel::Verdict
+}
+
+/// The method of measuring the memory usage of a program.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryAccounting {
+    /// The maximum resident set size of the program, which under-reports the memory usage of
+    /// programs running multiple processes.
+    #[default]
+    Rss,
+    /// The peak memory usage of the control group of the program, including all its processes.
+    Cgroup,
+}
+
+/// The policy for the feedback given for sample and hidden testcases.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FeedbackPolicy {
+    /// The number of bytes kept from each end of the captured files.
+    #[serde(default = "default_excerpt_bytes")]
+    pub excerpt_bytes: u64,
+    #[serde(default = "default_sample_feedback")]
+    pub sample: FeedbackLevel,
+    #[serde(default = "default_hidden_feedback")]
+    pub hidden: FeedbackLevel,
+}
+
+impl Default for FeedbackPolicy {
+    fn default() -> FeedbackPolicy {
+        FeedbackPolicy {
+            excerpt_bytes: default_excerpt_bytes(),
+            sample: default_sample_feedback(),
+            hidden: default_hidden_feedback(),
+        }
+    }
+}
+
+impl FeedbackPolicy {
+    pub fn level(&self, testcase: &Testcase) -> FeedbackLevel {
+        if testcase.sample {
+            self.sample
+        } else {
+            self.hidden
+        }
+    }
+}
+
+/// The policy for re-running testcases whose running time is close to the time limit, so that
+/// timing noise does not flip the verdict between AC and TLE.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RerunPolicy {
+    /// The maximum number of runs for each testcase, including the first run.
+    pub max_runs: usize,
+    /// The margin, as a percentage of the time limit, within which a finished run is considered
+    /// close to the time limit and is repeated.
+    #[serde(default)]
+    pub margin: f64,
+}
+
+/// A reference solution of the problem, tagged with the verdict it is expected to get.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Solution {
+    /// The path to the source code, relative to the metadata file.
+    pub source: String,
+    pub language: String,
+    pub expected: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Metadata {
+    pub problem_name: String,
+    pub time_limit: f64,
+    /// The limit (in seconds) of the wall time of the program. If not specified, the limit is
+    /// derived from the time limit, so that a program waiting for input or sleeping is stopped,
+    /// while a program slowed down by the load of the machine is not.
+    pub wall_time_limit: Option<f64>,
+    pub memory_limit: i64,
+    #[serde(default)]
+    pub memory_accounting: MemoryAccounting,
+    /// The limit (in KB) of the size of the files written by the program, including its output.
+    #[serde(default = "default_output_limit")]
+    pub output_limit: i64,
+    pub compile_time_limit: f64,
+    pub compile_memory_limit: i64,
+    /// The number of bytes kept from each end of the output and the error output of the
+    /// compiler.
+    #[serde(default = "default_compile_message_bytes")]
+    pub compile_message_bytes: u64,
+    pub checker_time_limit: f64,
+    pub checker_memory_limit: i64,
+    pub testcases: Vec<Testcase>,
+    /// The files copied next to the source before compiling it, e.g. a grader, relative to the
+    /// base path of the testcases files.
+    #[serde(default)]
+    pub extra_files: Vec<String>,
+    pub rerun: Option<RerunPolicy>,
+    #[serde(default)]
+    pub solutions: Vec<Solution>,
+    #[serde(default)]
+    pub feedback: FeedbackPolicy,
+}
+
+impl Metadata {
+    /// The wall time limit, twice the time limit plus one second if not specified.
+    pub fn effective_wall_time_limit(&self) -> f64 {
+        match self.wall_time_limit {
+            Some(wall_time_limit) => wall_time_limit,
+            None => self.time_limit * 2. + 1.,
+        }
+    }
+}
+
+pub fn read_metadata(metadata_path: &str) -> Result<Metadata> {
+    log::debug!("Reading metadata from {}...", &metadata_path);
+
+    let metadata_file = std::fs::File::open(metadata_path)?;
+    let mut metadata: Metadata = match serde_yaml::from_reader(metadata_file) {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            log::error!("Error when parsing metadata: {:?}", err);
+            return Err(err.into());
+        }
+    };
+
+    for (i, testcase) in metadata.testcases.iter_mut().enumerate() {
+        testcase.id = i;
+    }
+
+    Ok(metadata)
+}
+
+/// A problem with the paths to the files needed for judging.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub metadata: Metadata,
+    /// The path to be used as the base path of the test cases files.
+    pub testcases: String,
+    /// The path to the source code of the checker.
+    pub checker: String,
+    /// The language code for compiling the checker.
+    pub checker_language: String,
+    /// The path to testlib.h.
+    pub testlib: String,
+}
+
+/// Resolve the path to the source code of a reference solution.
+pub fn solution_path(metadata_path: &str, solution: &Solution) -> String {
+    let base = Path::new(metadata_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    base.join(&solution.source).to_string_lossy().to_string()
+}
@@ -0,0 +1,923 @@
+use crate::cache::{CacheEntry, CompileCache};
+use crate::error::{Error, Result};
+use crate::judge::{self, Excerpt, JudgeOutput, TestcaseOutput};
+use crate::languages::{Language, LanguageRegistry, TemplateContext};
+use crate::observer::Observer;
+use crate::problem::{FeedbackLevel, Metadata, Problem, Testcase};
+use crate::sandbox::{self, Sandbox, SandboxPool};
+use crate::state::AppState;
+/// This module contains the judging of submissions: compiling the checker and the source program,
+/// and running the testcases in parallel in the sandbox pool.
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+/// A submission to be judged.
+#[derive(Debug, Clone)]
+pub struct Submission {
+    /// The ID of the submission, attached to the progress events, e.g. when judging submissions
+    /// in a batch.
+    pub id: Option<String>,
+    /// The path to the file containing source code.
+    pub source: String,
+    /// The language code of the source code.
+    pub language: String,
+}
+
+/// A function writing the verdict of a submission, e.g. to a file, before the observers are
+/// notified that the submission is judged.
+pub type VerdictSink<'a> = Box<dyn Fn(&Problem, &Submission, &JudgeOutput) -> Result<()> + 'a>;
+
+/// A builder of `Judge`. The problem, the languages and the sandbox pool are required.
+#[derive(Default)]
+pub struct JudgeBuilder<'a> {
+    problem: Option<Problem>,
+    submission: Option<Submission>,
+    languages: Option<LanguageRegistry>,
+    sandboxes: Option<&'a SandboxPool>,
+    observers: Vec<Arc<dyn Observer>>,
+    compile_cache: Option<CompileCache>,
+    verdict_sink: Option<VerdictSink<'a>>,
+}
+
+impl<'a> JudgeBuilder<'a> {
+    pub fn problem(mut self, problem: Problem) -> Self {
+        self.problem = Some(problem);
+        self
+    }
+
+    pub fn submission(mut self, submission: Submission) -> Self {
+        self.submission = Some(submission);
+        self
+    }
+
+    pub fn languages(mut self, languages: LanguageRegistry) -> Self {
+        self.languages = Some(languages);
+        self
+    }
+
+    pub fn sandboxes(mut self, sandboxes: &'a SandboxPool) -> Self {
+        self.sandboxes = Some(sandboxes);
+        self
+    }
+
+    /// Reuse the compiled submissions in the cache, and store the newly compiled ones in it.
+    pub fn compile_cache(mut self, compile_cache: CompileCache) -> Self {
+        self.compile_cache = Some(compile_cache);
+        self
+    }
+
+    /// Add an observer to be notified of the progress of judging.
+    pub fn observer<O: Observer + 'static>(mut self, observer: O) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    /// Write the verdict of each submission with the function before the observers are notified,
+    /// so that the verdict is available when the observers announce it.
+    pub fn verdict_sink<F>(mut self, verdict_sink: F) -> Self
+    where
+        F: Fn(&Problem, &Submission, &JudgeOutput) -> Result<()> + 'a,
+    {
+        self.verdict_sink = Some(Box::new(verdict_sink));
+        self
+    }
+
+    pub fn build(self) -> Result<Judge<'a>> {
+        let missing =
+            |field: &str| Error::Option(format!("The {} of the judge is not specified.", field));
+
+        Ok(Judge {
+            problem: self.problem.ok_or_else(|| missing("problem"))?,
+            submission: self.submission,
+            languages: self.languages.ok_or_else(|| missing("languages"))?,
+            sandboxes: self.sandboxes.ok_or_else(|| missing("sandbox pool"))?,
+            observers: self.observers,
+            compile_cache: self.compile_cache,
+            verdict_sink: self.verdict_sink,
+            checker: Mutex::new(None),
+            versions: Mutex::new(HashMap::new()),
+        })
+    }
+}
+
+/// A judge of submissions against a problem, running in a sandbox pool.
+///
+/// ```no_run
+/// # use minijudge_rust::{Judge, Submission};
+/// # fn example(
+/// #     problem: minijudge_rust::problem::Problem,
+/// #     languages: minijudge_rust::languages::LanguageRegistry,
+/// # ) -> minijudge_rust::error::Result<()> {
+/// let sandboxes = minijudge_rust::sandbox::SandboxPool::create(4)?;
+/// let judge_output = Judge::builder()
+///     .problem(problem)
+///     .submission(Submission {
+///         id: None,
+///         source: "main.cpp".to_string(),
+///         language: "cpp17".to_string(),
+///     })
+///     .languages(languages)
+///     .sandboxes(&sandboxes)
+///     .build()?
+///     .run()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Judge<'a> {
+    problem: Problem,
+    submission: Option<Submission>,
+    languages: LanguageRegistry,
+    sandboxes: &'a SandboxPool,
+    observers: Vec<Arc<dyn Observer>>,
+    compile_cache: Option<CompileCache>,
+    verdict_sink: Option<VerdictSink<'a>>,
+    /// The compiled checker, kept outside of the sandboxes once compiled.
+    checker: Mutex<Option<Arc<Vec<u8>>>>,
+    /// The detected versions of the toolchains, by language code, detected once per language.
+    versions: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl<'a> Judge<'a> {
+    pub fn builder() -> JudgeBuilder<'a> {
+        JudgeBuilder::default()
+    }
+
+    pub fn problem(&self) -> &Problem {
+        &self.problem
+    }
+
+    /// Judge the submission given to the builder.
+    pub fn run(&self) -> Result<JudgeOutput> {
+        match &self.submission {
+            Some(submission) => self.judge(submission),
+            None => Err(Error::Option(
+                "The submission of the judge is not specified.".to_string(),
+            )),
+        }
+    }
+
+    /// Judge a submission. The sandboxes are cleared first, so that nothing is left from the
+    /// previous submission. The checker is compiled once, before the first submission is judged.
+    /// Errors in the judging process result in a system error verdict with the details of the
+    /// error, instead of an `Err`. The verdict is written to the verdict sink before the observers
+    /// are notified that the submission is judged.
+    pub fn judge(&self, submission: &Submission) -> Result<JudgeOutput> {
+        let source_language = self.find_language(&submission.language)?;
+
+        for observer in self.observers.iter() {
+            observer.on_submission_start(submission);
+        }
+
+        let result = self
+            .sandboxes
+            .clear()
+            .and_then(|()| self.prepare_checker())
+            .and_then(|checker| {
+                self.judge_submission(&submission.source, source_language, checker)
+            });
+
+        let mut judge_output = match result {
+            Ok(judge_output) => judge_output,
+            Err(err) => {
+                log::error!("Unexpected error: {}", err);
+                judge::system_error_output(self.problem.metadata.testcases.len(), &err)
+            }
+        };
+        self.record_versions(&mut judge_output, source_language);
+
+        if let Some(verdict_sink) = &self.verdict_sink {
+            verdict_sink(&self.problem, submission, &judge_output)?;
+        }
+        for observer in self.observers.iter() {
+            observer.on_submission_end(&judge_output);
+        }
+
+        Ok(judge_output)
+    }
+
+    fn find_language(&self, code: &str) -> Result<&Language> {
+        match self.languages.get(code) {
+            Some(language) => Ok(language),
+            None => Err(Error::Option(format!(
+                "The language {} is not defined.",
+                code
+            ))),
+        }
+    }
+
+    /// Record the versions of the toolchains of the submission and the checker in the verdict.
+    /// Failing to detect a version does not affect the verdict.
+    fn record_versions(&self, judge_output: &mut JudgeOutput, source_language: &Language) {
+        let mut languages = vec![source_language];
+        if let Ok(checker_language) = self.find_language(&self.problem.checker_language) {
+            languages.push(checker_language);
+        }
+
+        for language in languages {
+            if let Some(version) = self.toolchain_version(language) {
+                judge_output.versions.insert(language.code.clone(), version);
+            }
+        }
+    }
+
+    /// The version of the toolchain of the language, detected once per language.
+    fn toolchain_version(&self, language: &Language) -> Option<String> {
+        let mut versions = self.versions.lock().unwrap();
+        versions
+            .entry(language.code.clone())
+            .or_insert_with(|| match self.sandboxes.primary().detect_version(language) {
+                Ok(version) => version,
+                Err(err) => {
+                    log::warn!("Failed to detect the version of {}: {}", language.code, err);
+                    None
+                }
+            })
+            .clone()
+    }
+
+    /// Compile the submission in the primary sandbox, or reuse the compiled submission in the
+    /// cache if there is one.
+    fn compile_submission(
+        &self,
+        language: &Language,
+        context: &TemplateContext,
+        source: &[u8],
+    ) -> Result<CacheEntry> {
+        let sandbox_primary = self.sandboxes.primary();
+        let metadata = &self.problem.metadata;
+        let box_folder = sandbox_primary.file_path("");
+
+        let cache = match &self.compile_cache {
+            Some(compile_cache) => self
+                .compile_cache_key(language, context, source)?
+                .map(|key| (compile_cache, key)),
+            None => None,
+        };
+        if let Some((compile_cache, key)) = &cache {
+            if let Some(entry) = compile_cache.get(key, &box_folder) {
+                log::info!("Reusing the compiled submission {} in the cache.", key);
+                return Ok(entry);
+            }
+        }
+
+        let files_before = sandbox_primary.list_files()?;
+        let output = compile_source(sandbox_primary, language, metadata, context)?;
+        let meta_file = sandbox_primary
+            .read_file("compile_meta.txt")
+            .map_err(|_| Error::MetaMissing)?;
+        let meta = judge::parse_meta(&meta_file);
+        let verdict = judge::compile_verdict(&meta, output.status.success()).map(str::to_string);
+        let artifacts = match verdict {
+            Some(_) => vec![],
+            None => compiled_artifacts(
+                &sandbox::changed_files(&files_before, &sandbox_primary.list_files()?),
+                &context.executable,
+            ),
+        };
+        let entry = CacheEntry {
+            verdict,
+            compile: judge::CompileOutput::new(
+                &meta,
+                sandbox_primary.read_excerpt("compile_out.txt", metadata.compile_message_bytes)?,
+                sandbox_primary.read_excerpt("compile_err.txt", metadata.compile_message_bytes)?,
+            ),
+            artifacts,
+        };
+
+        // A compilation exceeding the time limit may succeed when rejudged, so it is not kept.
+        if let Some((compile_cache, key)) = &cache {
+            if entry.verdict.as_deref() != Some(judge::VERDICT_CTLE) {
+                if let Err(err) = compile_cache.put(key, &entry, &box_folder) {
+                    log::warn!(
+                        "Failed to store the compiled submission in the cache: {}",
+                        err
+                    );
+                }
+            }
+        }
+
+        Ok(entry)
+    }
+
+    /// The key of the compiled submission in the cache, covering everything affecting the
+    /// compilation, or `None` if the submission is not cached as the compiler version is unknown.
+    fn compile_cache_key(
+        &self,
+        language: &Language,
+        context: &TemplateContext,
+        source: &[u8],
+    ) -> Result<Option<String>> {
+        // Without the version, artifacts of an older compiler could be reused after an upgrade.
+        let version = match self.toolchain_version(language) {
+            Some(version) => version,
+            None => {
+                log::debug!(
+                    "The compiled submission is not cached, as the version of {} is unknown.",
+                    language.code
+                );
+                return Ok(None);
+            }
+        };
+
+        let metadata = &self.problem.metadata;
+        let mut parts = vec![
+            source.to_vec(),
+            serde_json::to_vec(language)?,
+            serde_json::to_vec(context)?,
+            version.into_bytes(),
+            format!(
+                "{} {} {}",
+                metadata.compile_time_limit,
+                metadata.compile_memory_limit,
+                metadata.compile_message_bytes
+            )
+            .into_bytes(),
+        ];
+        for extra_file in context.extra_files.iter() {
+            parts.push(std::fs::read(
+                self.sandboxes.primary().file_path(extra_file),
+            )?);
+        }
+
+        let parts: Vec<&[u8]> = parts.iter().map(|part| &part[..]).collect();
+        Ok(Some(CompileCache::key(&parts)))
+    }
+
+    /// Copy the extra files of the problem into the primary sandbox, returning their filenames.
+    fn copy_extra_files(&self) -> Result<Vec<String>> {
+        let mut filenames = vec![];
+        for extra_file in self.problem.metadata.extra_files.iter() {
+            let path = PathBuf::from(&self.problem.testcases).join(extra_file);
+            let filename = match path.file_name() {
+                Some(filename) => filename.to_string_lossy().to_string(),
+                None => {
+                    return Err(Error::Option(format!(
+                        "The extra file {} is not a file.",
+                        extra_file
+                    )))
+                }
+            };
+            self.sandboxes
+                .primary()
+                .copy_into(&path.to_string_lossy(), &filename)?;
+            filenames.push(filename);
+        }
+
+        Ok(filenames)
+    }
+
+    /// Compile the checker in the primary sandbox and keep it outside of the sandboxes, so that
+    /// submissions cannot modify it.
+    fn prepare_checker(&self) -> Result<Arc<Vec<u8>>> {
+        let mut checker = self.checker.lock().unwrap();
+        if let Some(checker) = checker.as_ref() {
+            return Ok(checker.clone());
+        }
+
+        let sandbox_primary = self.sandboxes.primary();
+
+        sandbox_primary.copy_into(&self.problem.testlib, "./testlib.h")?;
+        sandbox_primary.copy_into(&self.problem.checker, "./checker.cpp")?;
+
+        let checker_language = self.find_language(&self.problem.checker_language)?;
+        let metadata = &self.problem.metadata;
+        let context = TemplateContext {
+            memory_limit: metadata.checker_memory_limit,
+            time_limit: metadata.checker_time_limit,
+            stack_size: checker_language.stack_size(metadata.checker_memory_limit),
+            ..TemplateContext::new("checker.cpp", "checker", "")
+        };
+        let output = compile_checker(sandbox_primary, checker_language, metadata, &context)?;
+
+        if !output.status.success() {
+            let compile_message = String::from_utf8_lossy(&output.stderr).to_string();
+            log::error!("Error when compiling checker:\n{}", compile_message);
+            return Err(Error::CheckerCompile(compile_message));
+        }
+
+        let compiled = Arc::new(std::fs::read(sandbox_primary.file_path("checker"))?);
+        std::fs::remove_file(sandbox_primary.file_path("checker"))?;
+        *checker = Some(compiled.clone());
+
+        Ok(compiled)
+    }
+
+    /// Compile the source program and judge it against all the testcases.
+    fn judge_submission(
+        &self,
+        source: &str,
+        source_language: &Language,
+        checker: Arc<Vec<u8>>,
+    ) -> Result<JudgeOutput> {
+        let metadata = &self.problem.metadata;
+
+        // Generate a list of testcases for judge to consume.
+        let testcases_stack: Arc<Mutex<Vec<Testcase>>> = Arc::new(Mutex::new(
+            metadata
+                .testcases
+                .clone()
+                .into_iter()
+                .rev()
+                .collect::<Vec<Testcase>>(),
+        ));
+
+        let mut judge_output = judge::new_judge_output(metadata.testcases.len());
+        judge_output.memory_accounting = Some(metadata.memory_accounting);
+        judge_output.memory_baseline = source_language.memory_baseline;
+
+        let sandbox_primary = self.sandboxes.primary();
+        // The filenames may depend on the source, e.g. on the public class in Java.
+        let source_bytes = std::fs::read(source)?;
+        let source_code = String::from_utf8_lossy(&source_bytes).to_string();
+        let (source_file, executable_file) = source_language.filenames(&source_code)?;
+
+        sandbox_primary.copy_into(source, &source_file)?;
+        let context = TemplateContext {
+            memory_limit: metadata.memory_limit,
+            time_limit: metadata.time_limit,
+            stack_size: source_language.stack_size(metadata.memory_limit),
+            extra_files: self.copy_extra_files()?,
+            ..TemplateContext::new(&source_file, &executable_file, &source_code)
+        };
+
+        for observer in self.observers.iter() {
+            observer.on_compile_start(&source_language.code);
+        }
+
+        let compile_result = self.compile_submission(source_language, &context, &source_bytes);
+        if let Ok(entry) = &compile_result {
+            judge_output.compile_message = entry.compile.message();
+            judge_output.compile = Some(entry.compile.clone());
+        }
+
+        let compiled = match &compile_result {
+            Ok(entry) => entry.verdict.is_none(),
+            Err(_) => false,
+        };
+        for observer in self.observers.iter() {
+            observer.on_compile_end(compiled, &judge_output.compile_message);
+        }
+
+        let entry = compile_result?;
+        if let Some(verdict) = &entry.verdict {
+            judge::fill_verdict(&mut judge_output, verdict);
+            return Ok(judge_output);
+        }
+
+        // Copy the compiled binaries to other sandboxes.
+        self.sandboxes.distribute_all(&entry.artifacts)?;
+
+        let judge_output = Arc::new(Mutex::new(judge_output));
+        let state = Arc::new(AppState {
+            problem: self.problem.clone(),
+            language: source_language.clone(),
+            context,
+            checker,
+            judge_output: judge_output.clone(),
+            testcases_stack,
+            observers: self.observers.clone(),
+        });
+
+        // Launch the judge threads.
+        let mut threads: Vec<thread::JoinHandle<()>> = Vec::new();
+        for (thread_id, thread_sb) in self.sandboxes.sandboxes.iter().enumerate() {
+            // Clone the variables to be passed into the thread.
+            let thread_sb = thread_sb.clone();
+            let state = state.clone();
+
+            let thread = thread::spawn(move || {
+                judge_thread(thread_id, thread_sb, state);
+            });
+
+            threads.push(thread);
+        }
+
+        // Wait for all threads to finish.
+        for thread in threads {
+            if thread.join().is_err() {
+                log::error!("A judge thread terminated unexpectedly.");
+            }
+        }
+
+        let mut judge_output = judge_output
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        // Testcases left by a thread terminated unexpectedly are not judged.
+        let err = Error::Panic("The testcase was left unjudged by its judge thread.".to_string());
+        for testcase_output in judge_output.testcases.iter_mut() {
+            if testcase_output.verdict == judge::VERDICT_WJ {
+                judge::set_system_error(testcase_output, &err);
+            }
+        }
+
+        // Compute overall verdict, time and memory
+        judge::calc_overall_verdict(&mut judge_output);
+
+        Ok(judge_output)
+    }
+}
+
+fn judge_thread(thread_id: usize, thread_sb: Sandbox, state: Arc<AppState>) {
+    log::debug!(
+        "Thread {} spawned. Sandbox at {:?}.",
+        thread_id,
+        &thread_sb.path
+    );
+
+    let AppState {
+        judge_output,
+        testcases_stack,
+        observers,
+        ..
+    } = state.as_ref();
+
+    loop {
+        let testcase: Option<Testcase> = testcases_stack.lock().unwrap().pop();
+
+        let testcase = match testcase {
+            Some(testcase) => testcase,
+            None => {
+                log::debug!("Thread {} finds no test cases and terminates.", thread_id);
+                break;
+            }
+        };
+
+        let id = testcase.id;
+        for observer in observers.iter() {
+            observer.on_testcase_start(id);
+        }
+
+        let mut testcase_output: TestcaseOutput =
+            judge_output.lock().unwrap().testcases[id].clone();
+
+        // A panic when judging the testcase results in a system error of the testcase, instead of
+        // aborting the whole judge.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            judge_testcase(&thread_sb, &state, &testcase, &mut testcase_output)
+        }));
+        let err = match result {
+            Ok(Ok(())) => None,
+            Ok(Err(err)) => Some(err),
+            Err(payload) => Some(Error::from_panic(payload.as_ref())),
+        };
+        if let Some(err) = err {
+            log::error!("Test {} failed with system error: {}", id, err);
+            judge::set_system_error(&mut testcase_output, &err);
+        }
+
+        // Output and store the result of the testcase as needed.
+        judge_output.lock().unwrap().testcases[id] = testcase_output.clone();
+
+        let total_testcases = judge_output.lock().unwrap().testcases.len();
+        let remaining_testcases = testcases_stack.lock().unwrap().len();
+        let processed_testcases = total_testcases - remaining_testcases;
+
+        log::debug!(
+            "Progress: {} processed / {} total",
+            processed_testcases,
+            total_testcases
+        );
+        log::debug!("Test {} processing completed by thread {}.", id, thread_id);
+        log::debug!(
+            "Test {}: Verdict = {}, Time = {}, Memory = {}",
+            id,
+            testcase_output.verdict,
+            testcase_output.time,
+            testcase_output.memory,
+        );
+
+        for observer in observers.iter() {
+            observer.on_testcase_end(id, &testcase_output);
+        }
+    }
+}
+
+/// Run a testcase in the sandbox and check its output, storing the result in `testcase_output`.
+fn judge_testcase(
+    thread_sb: &Sandbox,
+    state: &AppState,
+    testcase: &Testcase,
+    testcase_output: &mut TestcaseOutput,
+) -> Result<()> {
+    let AppState {
+        problem,
+        language: source_language,
+        context,
+        checker,
+        ..
+    } = state;
+    let metadata = &problem.metadata;
+    let Testcase {
+        id, input, output, ..
+    } = testcase;
+
+    thread_sb.copy_into(
+        &PathBuf::from(&problem.testcases)
+            .join(input)
+            .to_string_lossy(),
+        "in.txt",
+    )?;
+
+    // Run the testcase, repeating the run if the policy asks for it. The run with the minimum
+    // CPU time is kept, together with its output.
+    let max_runs = match &metadata.rerun {
+        Some(policy) => policy.max_runs.max(1),
+        None => 1,
+    };
+    let output_file = if max_runs > 1 { "best.txt" } else { "out.txt" };
+    let error_file = if max_runs > 1 {
+        "best_err.txt"
+    } else {
+        "err.txt"
+    };
+    let mut best: Option<(judge::Meta, String)> = None;
+
+    for run in 0..max_runs {
+        log::trace!("Test {} executing (run {}).", id, run + 1);
+        thread_sb.run(
+            source_language,
+            &sandbox::ExecuteConfig {
+                memory_limit: metadata.memory_limit,
+                // The file size limit of the sandbox is 1 KB above the output limit, so that
+                // output of exactly the limit is accepted, and output over the limit is detected
+                // even if the program survives the file size limit.
+                output_limit: Some(metadata.output_limit + 1),
+                time_limit: metadata.time_limit,
+                wall_time_limit: metadata.effective_wall_time_limit(),
+                meta_file: Some("meta.txt"),
+                full_env: false,
+                unlimited_processes: false,
+                input_file: Some("in.txt"),
+                output_file: Some("out.txt"),
+                error_file: Some("err.txt"),
+                ..sandbox::ExecuteConfig::default()
+            },
+            context,
+        )?;
+        log::trace!("Test {} executed.", id);
+
+        let meta_file = thread_sb
+            .read_file("meta.txt")
+            .map_err(|_| Error::MetaMissing)?;
+        let meta = judge::parse_meta(&meta_file);
+        let meta = judge::apply_output_limit(
+            &meta,
+            thread_sb.file_size("out.txt")?,
+            metadata.output_limit,
+        );
+        let meta = judge::account_memory(
+            &meta,
+            metadata.memory_accounting,
+            source_language.memory_baseline,
+        );
+        let meta = judge::apply_memory_limit(&meta, metadata.memory_limit);
+        let meta = judge::apply_wall_time_limit(&meta, metadata.time_limit);
+        testcase_output
+            .time_measurements
+            .push(meta.time.unwrap_or(0.));
+
+        let is_best = match &best {
+            Some((best_meta, _)) => {
+                meta.time.unwrap_or(f64::INFINITY) < best_meta.time.unwrap_or(f64::INFINITY)
+            }
+            None => true,
+        };
+        if is_best {
+            if max_runs > 1 {
+                thread_sb.copy_across_sandbox(thread_sb, "out.txt", output_file)?;
+                thread_sb.copy_across_sandbox(thread_sb, "err.txt", error_file)?;
+            }
+            best = Some((meta, meta_file));
+        }
+
+        match (&metadata.rerun, &best) {
+            (Some(policy), Some((best_meta, _)))
+                if judge::should_rerun(best_meta, metadata.time_limit, policy) =>
+            {
+                log::debug!("Test {} is close to the time limit and is run again.", id);
+            }
+            _ => break,
+        }
+    }
+
+    let (meta, meta_file) = match best {
+        Some(best) => best,
+        None => return Err(Error::MetaMissing),
+    };
+
+    if let Some(time) = &meta.time {
+        testcase_output.time = *time;
+    }
+    if let Some(memory) = &meta.memory {
+        testcase_output.memory = *memory;
+    }
+    if let Some(verdict) = &meta.verdict {
+        testcase_output.verdict = verdict.clone();
+    }
+    testcase_output.detail = meta.detail.clone();
+
+    testcase_output.sandbox_output = meta_file;
+
+    // The excerpts are kept for feedback only, so failing to read them is not an error.
+    let feedback = &metadata.feedback;
+    let level = feedback.level(testcase);
+    if level >= FeedbackLevel::Output {
+        testcase_output.stdout = thread_sb
+            .read_excerpt(output_file, feedback.excerpt_bytes)
+            .ok();
+        testcase_output.stderr = thread_sb
+            .read_excerpt(error_file, feedback.excerpt_bytes)
+            .ok();
+    }
+    if level >= FeedbackLevel::Full {
+        let read = |file: &str| {
+            Excerpt::read(
+                &PathBuf::from(&problem.testcases).join(file),
+                feedback.excerpt_bytes,
+            )
+            .ok()
+        };
+        testcase_output.input = read(input);
+        testcase_output.answer = read(output);
+    }
+
+    if meta.verdict.is_some() {
+        return Ok(());
+    }
+
+    // The checker and the input are written again, as the submission may have modified them.
+    thread_sb.write_executable("checker", checker)?;
+    thread_sb.copy_into(
+        &PathBuf::from(&problem.testcases)
+            .join(input)
+            .to_string_lossy(),
+        "in.txt",
+    )?;
+    thread_sb.copy_into(
+        &PathBuf::from(&problem.testcases)
+            .join(output)
+            .to_string_lossy(),
+        "ans.txt",
+    )?;
+    let flags = vec!["checker", "in.txt", output_file, "ans.txt"];
+
+    log::trace!("Test {} checker executing.", id);
+    thread_sb
+        .execute(
+            &sandbox::ExecuteConfig {
+                memory_limit: metadata.checker_memory_limit,
+                time_limit: metadata.checker_time_limit,
+                wall_time_limit: metadata.checker_time_limit,
+                error_file: Some("checker.txt"),
+                ..sandbox::ExecuteConfig::default()
+            },
+            &flags,
+        )
+        .map_err(|err| Error::Checker(err.to_string()))?;
+    log::trace!("Test {} checker executed.", id);
+
+    let checker_output = thread_sb
+        .read_file("checker.txt")
+        .map_err(|err| Error::Checker(format!("Failed to read checker output: {}", err)))?
+        .trim()
+        .to_string();
+    testcase_output.checker_output = checker_output.clone();
+
+    // The testlib checkers report failures of the checker itself with "FAIL".
+    if checker_output.starts_with("FAIL") {
+        return Err(Error::Checker(checker_output));
+    }
+
+    let meta = judge::apply_checker_output(&meta, &checker_output);
+
+    if let Some(verdict) = meta.verdict {
+        testcase_output.verdict = verdict;
+    }
+
+    Ok(())
+}
+
+/// A helper function for compiling the source program.
+fn compile_source(
+    sb: &Sandbox,
+    language: &Language,
+    metadata: &Metadata,
+    context: &TemplateContext,
+) -> Result<std::process::Output> {
+    sb.compile(
+        language,
+        &sandbox::ExecuteConfig {
+            memory_limit: metadata.compile_memory_limit,
+            time_limit: metadata.compile_time_limit,
+            wall_time_limit: metadata.compile_time_limit,
+            meta_file: Some("compile_meta.txt"),
+            full_env: true,
+            unlimited_processes: true,
+            input_file: None,
+            output_file: Some("compile_out.txt"),
+            error_file: Some("compile_err.txt"),
+            ..sandbox::ExecuteConfig::default()
+        },
+        context,
+    )
+}
+
+/// The files produced by compiling a submission, i.e. the files changed by the compilation except
+/// for its logs. The executable is always included, even if the compilation leaves it unchanged.
+fn compiled_artifacts(changed_files: &[String], executable: &str) -> Vec<String> {
+    const COMPILE_LOGS: &[&str] = &["compile_meta.txt", "compile_out.txt", "compile_err.txt"];
+
+    let mut artifacts: Vec<String> = changed_files
+        .iter()
+        .filter(|file| !COMPILE_LOGS.contains(&file.as_str()))
+        .cloned()
+        .collect();
+    if !artifacts.iter().any(|file| file == executable) {
+        artifacts.push(executable.to_string());
+    }
+
+    artifacts
+}
+
+/// A helper function for compiling the checker.
+fn compile_checker(
+    sb: &Sandbox,
+    language: &Language,
+    metadata: &Metadata,
+    context: &TemplateContext,
+) -> Result<std::process::Output> {
+    sb.compile(
+        language,
+        &sandbox::ExecuteConfig {
+            memory_limit: metadata.compile_memory_limit,
+            time_limit: metadata.compile_time_limit,
+            wall_time_limit: metadata.compile_time_limit,
+            meta_file: None,
+            full_env: true,
+            unlimited_processes: true,
+            input_file: None,
+            output_file: None,
+            error_file: None,
+            additional_flags: Some(vec!["--full-env"]),
+            ..sandbox::ExecuteConfig::default()
+        },
+        context,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clear_sandboxes() {
+        let folder = std::env::temp_dir().join(format!("minijudge-judge-{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("box/pkg")).unwrap();
+        std::fs::write(folder.join("box/best.txt"), "previous").unwrap();
+        std::fs::write(folder.join("box/pkg/Main$Inner.class"), "previous").unwrap();
+        let sandboxes = SandboxPool::from_sandboxes(vec![Sandbox {
+            path: folder.clone(),
+            id: 9999,
+        }]);
+
+        let metadata = serde_yaml::from_str(
+            "problem_name: A\ntime_limit: 1\nwall_time_limit: null\nmemory_limit: 262144\n\
+             compile_time_limit: 10\ncompile_memory_limit: 262144\nchecker_time_limit: 10\n\
+             checker_memory_limit: 262144\nrerun: null\ntestcases: []\n",
+        )
+        .unwrap();
+        let problem = Problem {
+            metadata,
+            testcases: folder.to_string_lossy().to_string(),
+            checker: "missing_checker.cpp".to_string(),
+            checker_language: "cpp17".to_string(),
+            testlib: "missing_testlib.h".to_string(),
+        };
+        let judge = Judge::builder()
+            .problem(problem)
+            .languages(LanguageRegistry::load("languages.yml").unwrap())
+            .sandboxes(&sandboxes)
+            .build()
+            .unwrap();
+
+        // The checker is missing, so the submission gets a system error after the sandboxes are
+        // cleared.
+        let judge_output = judge
+            .judge(&Submission {
+                id: None,
+                source: "missing.cpp".to_string(),
+                language: "cpp17".to_string(),
+            })
+            .unwrap();
+        let leftover = std::fs::read_dir(folder.join("box")).unwrap().count();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(judge_output.verdict, judge::VERDICT_SE);
+        assert_eq!(leftover, 0);
+    }
+}
@@ -1,9 +1,19 @@
-use crate::languages::Language;
+use crate::error::{Error, Result};
+use crate::judge::Excerpt;
+use crate::languages::{Language, Limit, RuntimeSettings, TemplateContext};
 use std::clone::Clone;
+use std::collections::{BTreeMap, BTreeSet};
 use std::default::Default;
 use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Output};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Clone)]
 pub struct Sandbox {
@@ -18,11 +28,194 @@ impl Sandbox {
     }
 }
 
+/// The folder holding the lock files of the sandboxes in use, shared by all judge processes.
+const LOCK_FOLDER: &str = "minijudge-rust";
+
+/// The IDs of the sandboxes created by the pools of this process, cleaned up by
+/// `cleanup_active_sandboxes` when the process is interrupted.
+static ACTIVE_SANDBOXES: Mutex<BTreeSet<i32>> = Mutex::new(BTreeSet::new());
+
+/// The process IDs of the isolate processes running programs, killed by
+/// `cleanup_active_sandboxes` before the sandboxes are cleaned up.
+static RUNNING_PROCESSES: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+/// Whether the process is interrupted, after which no more programs are run.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The time to wait for the running programs to be killed before cleaning up the sandboxes.
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A lock on a sandbox ID, held by a judge process as long as it uses the sandbox, so that
+/// concurrent processes never clean up each other's sandboxes. The lock is released by the
+/// operating system when the process exits, even if it is killed.
+struct SandboxLock {
+    _file: File,
+}
+
+impl SandboxLock {
+    fn acquire(box_id: i32) -> Result<SandboxLock> {
+        let folder = std::env::temp_dir().join(LOCK_FOLDER);
+        std::fs::create_dir_all(&folder)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(folder.join(format!("box-{}.lock", box_id)))?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return Err(Error::Sandbox(format!(
+                "Sandbox {} is used by another judge process. Use --box-id-offset to give concurrent judge processes disjoint sandboxes.",
+                box_id
+            )));
+        }
+
+        Ok(SandboxLock { _file: file })
+    }
+}
+
+/// A set of sandboxes for judging testcases in parallel. The first sandbox is the primary sandbox,
+/// in which programs are compiled before being copied to the other sandboxes. The sandboxes are
+/// cleaned up when the pool is dropped.
+pub struct SandboxPool {
+    pub sandboxes: Vec<Sandbox>,
+    locks: Vec<SandboxLock>,
+}
+
+impl SandboxPool {
+    /// Create the sandboxes with IDs `0..count`.
+    pub fn create(count: i32) -> Result<SandboxPool> {
+        SandboxPool::create_with_offset(count, 0)
+    }
+
+    /// Create the sandboxes with IDs `box_id_offset..box_id_offset + count`. Fails if any of the
+    /// sandboxes is used by another judge process.
+    pub fn create_with_offset(count: i32, box_id_offset: i32) -> Result<SandboxPool> {
+        if count < 1 {
+            return Err(Error::Option(
+                "At least one sandbox must be created.".to_string(),
+            ));
+        }
+        if box_id_offset < 0 {
+            return Err(Error::Option(
+                "The box ID offset must not be negative.".to_string(),
+            ));
+        }
+
+        // The sandboxes created so far are cleaned up when the pool is dropped, even if creating
+        // the rest fails.
+        let mut pool = SandboxPool {
+            sandboxes: Vec::new(),
+            locks: Vec::new(),
+        };
+        for box_id in box_id_offset..box_id_offset + count {
+            pool.locks.push(SandboxLock::acquire(box_id)?);
+            pool.sandboxes.push(Sandbox::create(box_id)?);
+            ACTIVE_SANDBOXES.lock().unwrap().insert(box_id);
+        }
+
+        Ok(pool)
+    }
+
+    /// A pool of sandboxes which are already created, without holding their locks.
+    #[cfg(test)]
+    pub(crate) fn from_sandboxes(sandboxes: Vec<Sandbox>) -> SandboxPool {
+        SandboxPool {
+            sandboxes,
+            locks: Vec::new(),
+        }
+    }
+
+    pub fn primary(&self) -> &Sandbox {
+        &self.sandboxes[0]
+    }
+
+    /// Remove all the files in the sandboxes, e.g. left by the previous submission.
+    pub fn clear(&self) -> Result<()> {
+        for sandbox in self.sandboxes.iter() {
+            sandbox.clear()?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy a file from the primary sandbox to all the other sandboxes.
+    pub fn distribute(&self, source: &str, destination: &str) -> Result<()> {
+        for sb_sub in self.sandboxes.iter().skip(1) {
+            self.primary()
+                .copy_across_sandbox(sb_sub, source, destination)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy files from the primary sandbox to all the other sandboxes, keeping their paths.
+    pub fn distribute_all(&self, files: &[String]) -> Result<()> {
+        for file in files {
+            self.distribute(file, file)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for SandboxPool {
+    fn drop(&mut self) {
+        for sandbox in self.sandboxes.iter() {
+            if ACTIVE_SANDBOXES.lock().unwrap().remove(&sandbox.id) {
+                if let Err(err) = Sandbox::cleanup(sandbox.id) {
+                    log::warn!("{}", err);
+                }
+            }
+        }
+        // The locks are released only after the sandboxes are cleaned up.
+        self.locks.clear();
+    }
+}
+
+/// Clean up the sandboxes of all pools in this process, e.g. when the process is interrupted by a
+/// signal and the pools are not dropped. The running programs are killed first, and no more
+/// programs are run afterwards, so that no sandbox is cleaned up while a program runs in it.
+pub fn cleanup_active_sandboxes() {
+    kill_running_processes();
+
+    let box_ids = std::mem::take(&mut *ACTIVE_SANDBOXES.lock().unwrap());
+    for box_id in box_ids {
+        if let Err(err) = Sandbox::cleanup(box_id) {
+            log::warn!("{}", err);
+        }
+    }
+}
+
+/// Stop running programs, and terminate the running isolate processes, which kill their programs
+/// before exiting. Waits until the processes exit, or until the timeout.
+fn kill_running_processes() {
+    {
+        let running = RUNNING_PROCESSES.lock().unwrap();
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        for pid in running.iter() {
+            unsafe {
+                libc::kill(*pid as libc::pid_t, libc::SIGTERM);
+            }
+        }
+    }
+
+    let start = Instant::now();
+    while !RUNNING_PROCESSES.lock().unwrap().is_empty() {
+        if start.elapsed() > KILL_TIMEOUT {
+            log::warn!("The running programs did not exit before the sandboxes are cleaned up.");
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
 #[derive(Clone)]
 pub struct ExecuteConfig<'a> {
     pub wall_time_limit: f64,
     pub time_limit: f64,
     pub memory_limit: i64,
+    /// The limit (in KB) of the size of each file written by the program.
+    pub output_limit: Option<i64>,
     pub meta_file: Option<&'a str>,
     pub input_file: Option<&'a str>,
     pub output_file: Option<&'a str>,
@@ -38,6 +231,7 @@ impl Default for ExecuteConfig<'_> {
             wall_time_limit: 0.,
             time_limit: 0.,
             memory_limit: 0,
+            output_limit: None,
             meta_file: None,
             input_file: None,
             output_file: None,
@@ -49,16 +243,62 @@ impl Default for ExecuteConfig<'_> {
     }
 }
 
+/// Translate the runtime settings of a language to the flags of isolate.
+fn runtime_flags(runtime: &RuntimeSettings) -> Vec<String> {
+    // Isolate lifts the limits given as 0.
+    let limit = |limit: &Limit| match limit {
+        Limit::Unlimited => 0,
+        Limit::Value(value) => *value,
+    };
+
+    let mut flags = vec![];
+    if let Some(stack) = &runtime.stack {
+        flags.push(format!("--stack={}", limit(stack)));
+    }
+    if let Some(processes) = &runtime.processes {
+        flags.push(format!("--processes={}", limit(processes)));
+    }
+    if let Some(open_files) = &runtime.open_files {
+        flags.push(format!("--open-files={}", limit(open_files)));
+    }
+    for (name, value) in runtime.env.iter() {
+        flags.push(format!("--env={}={}", name, value));
+    }
+    for dir in runtime.dirs.iter() {
+        let mut rule = match &dir.outside {
+            Some(outside) => format!("--dir={}={}", dir.inside, outside),
+            None => format!("--dir={}", dir.inside),
+        };
+        for option in dir.options.iter() {
+            rule.push(':');
+            rule.push_str(option);
+        }
+        flags.push(rule);
+    }
+
+    flags
+}
+
 impl Sandbox {
-    pub fn create(box_id: i32) -> Result<Sandbox, Box<dyn std::error::Error>> {
-        // Ensure that there is no sandbox already created.
+    /// Create the sandbox with the ID. This is only done by a pool, which holds the lock on the ID.
+    fn create(box_id: i32) -> Result<Sandbox> {
+        // Ensure that there is no sandbox already created, e.g. left by a judge process that was
+        // killed.
         Sandbox::cleanup(box_id)?;
 
         let box_id_flag = format!("--box-id={}", box_id);
         let process = Command::new("isolate")
-            .args(&["--cg", "--init", &box_id_flag[..]])
+            .args(["--cg", "--init", &box_id_flag[..]])
             .output()?;
 
+        if !process.status.success() {
+            return Err(Error::Sandbox(format!(
+                "Failed to create sandbox {}: {}",
+                box_id,
+                String::from_utf8_lossy(&process.stderr).trim()
+            )));
+        }
+
         let sandbox_path = String::from_utf8_lossy(&process.stdout).trim().to_string();
 
         log::trace!("Sandbox {} created at {}.", box_id, &sandbox_path);
@@ -69,41 +309,48 @@ impl Sandbox {
         })
     }
 
-    pub fn cleanup(box_id: i32) -> Result<(), Box<dyn std::error::Error>> {
+    /// Clean up the sandbox with the ID, which must not be used by another judge process.
+    fn cleanup(box_id: i32) -> Result<()> {
         let box_id_flag = format!("--box-id={}", box_id);
         let process = Command::new("isolate")
-            .args(&["--cg", "--cleanup", &box_id_flag[..]])
+            .args(["--cg", "--cleanup", &box_id_flag[..]])
             .output()?;
 
-        assert!(process.status.success(), true);
+        if !process.status.success() {
+            return Err(Error::Sandbox(format!(
+                "Failed to clean up sandbox {}: {}",
+                box_id,
+                String::from_utf8_lossy(&process.stderr).trim()
+            )));
+        }
         log::trace!("Sandbox {} destroyed.", box_id);
 
         Ok(())
     }
 
-    pub fn execute(
-        &self,
-        config: &ExecuteConfig,
-        command: &[&str],
-    ) -> Result<std::process::Output, Box<dyn std::error::Error>> {
+    pub fn execute(&self, config: &ExecuteConfig, command: &[&str]) -> Result<Output> {
         let box_id_flag = format!("--box-id={}", self.id);
         let wall_time_flag = format!("--wall-time={}", config.wall_time_limit);
         let time_flag = format!("--time={}", config.time_limit);
-        let memory_flag = format!("--mem={}", config.memory_limit);
+        // The memory of the whole control group is limited rather than the address space of each
+        // process, so that exceeding the limit kills the program with the out-of-memory killer
+        // instead of failing an allocation, and runtimes reserving address space are not affected.
+        let memory_flag = format!("--cg-mem={}", config.memory_limit);
 
         let input_flag: String;
         let output_flag: String;
         let error_flag: String;
         let meta_flag: String;
+        let output_limit_flag: String;
 
-        let mut args: Vec<&str> = Vec::new();
-
-        args.push("--cg");
-        args.push(&box_id_flag[..]);
-        args.push(&wall_time_flag[..]);
-        args.push(&time_flag[..]);
-        args.push(&memory_flag[..]);
-        args.push("--run");
+        let mut args: Vec<&str> = vec![
+            "--cg",
+            &box_id_flag[..],
+            &wall_time_flag[..],
+            &time_flag[..],
+            &memory_flag[..],
+            "--run",
+        ];
 
         if let Some(input_file) = config.input_file {
             input_flag = format!("--stdin={}", input_file);
@@ -125,6 +372,11 @@ impl Sandbox {
             args.push(&meta_flag);
         }
 
+        if let Some(output_limit) = config.output_limit {
+            output_limit_flag = format!("--fsize={}", output_limit);
+            args.push(&output_limit_flag);
+        }
+
         if config.full_env {
             args.push("--full-env");
         }
@@ -144,14 +396,43 @@ impl Sandbox {
             args.push(piece);
         }
 
-        let output = Command::new("isolate")
-            .current_dir(self.get_box_path())
-            .args(&args)
-            .output()?;
+        // The process is registered while the registry is locked, so that it is either killed by
+        // `cleanup_active_sandboxes` or not started at all.
+        let child = {
+            let mut running = RUNNING_PROCESSES.lock().unwrap();
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                return Err(Error::Sandbox(
+                    "The judge is interrupted, so no more programs are run.".to_string(),
+                ));
+            }
+            let child = Command::new("isolate")
+                .current_dir(self.get_box_path())
+                .args(&args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|err| Error::Sandbox(format!("Failed to run isolate: {}", err)))?;
+            running.insert(child.id());
+            child
+        };
+        let pid = child.id();
+        let output = child.wait_with_output();
+        RUNNING_PROCESSES.lock().unwrap().remove(&pid);
+        let output =
+            output.map_err(|err| Error::Sandbox(format!("Failed to run isolate: {}", err)))?;
 
         log::trace!("Stdout: {}", String::from_utf8_lossy(&output.stdout));
         log::trace!("Stderr: {}", String::from_utf8_lossy(&output.stderr));
-        log::trace!("Status: {}", output.status.code().unwrap());
+        log::trace!("Status: {:?}", output.status.code());
+
+        // The isolate sandbox exits with status 2 when it fails by itself, as opposed to the
+        // program inside failing.
+        if output.status.code() == Some(2) {
+            return Err(Error::Sandbox(
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
 
         Ok(output)
     }
@@ -160,10 +441,9 @@ impl Sandbox {
         &self,
         language: &Language,
         config: &ExecuteConfig,
-        source: &str,
-        destination: &str,
-    ) -> Result<std::process::Output, Box<dyn std::error::Error>> {
-        let flags: Vec<String> = language.compile(source, destination);
+        context: &TemplateContext,
+    ) -> Result<Output> {
+        let flags: Vec<String> = language.compile(context)?;
         let flags_str: Vec<&str> = flags.iter().map(|s| &s[..]).collect();
 
         let mut additional_flags = vec![];
@@ -192,9 +472,9 @@ impl Sandbox {
 
         log::trace!(
             "Compiled {} [{}] from {}.",
-            destination,
+            context.destination,
             language.code,
-            source
+            context.source
         );
         log::trace!(
             "  Compile stdout: {}",
@@ -212,10 +492,11 @@ impl Sandbox {
         &self,
         language: &Language,
         config: &ExecuteConfig,
-        executable: &str,
-    ) -> Result<Output, Box<dyn std::error::Error>> {
-        let flags: Vec<String> = language.execute(executable);
+        context: &TemplateContext,
+    ) -> Result<Output> {
+        let flags: Vec<String> = language.execute(context)?;
         let flags_str: Vec<&str> = flags.iter().map(|s| &s[..]).collect();
+        let runtime_flags = runtime_flags(&language.runtime);
 
         let mut additional_flags = vec![];
         if let Some(flags) = &config.additional_flags {
@@ -223,6 +504,9 @@ impl Sandbox {
                 additional_flags.push(flag);
             }
         }
+        for flag in runtime_flags.iter() {
+            additional_flags.push(flag);
+        }
         if let Some(flags) = &language.execute_flags {
             for flag in flags {
                 additional_flags.push(flag);
@@ -241,24 +525,63 @@ impl Sandbox {
 
         let output = self.execute(&config, &flags_str)?;
 
-        log::trace!("Run {} [{}] finished.", executable, language.code);
+        log::trace!("Run {} [{}] finished.", context.executable, language.code);
         log::trace!("  Run stdout: {}", String::from_utf8_lossy(&output.stdout));
         log::trace!("  Run stderr: {}", String::from_utf8_lossy(&output.stderr));
 
         Ok(output)
     }
 
+    /// Run the version command of the language, returning the first line of its output, or
+    /// `None` if the language has no version command.
+    pub fn detect_version(&self, language: &Language) -> Result<Option<String>> {
+        let version_command = match &language.version_command {
+            Some(version_command) => version_command,
+            None => return Ok(None),
+        };
+        let command: Vec<&str> = version_command.iter().map(|s| &s[..]).collect();
+
+        // Some toolchains, e.g. Java, print the version to the standard error.
+        let output_file = "version.txt";
+        let output = self.execute(
+            &ExecuteConfig {
+                time_limit: 10.,
+                wall_time_limit: 10.,
+                memory_limit: 1048576,
+                output_file: Some(output_file),
+                full_env: true,
+                unlimited_processes: true,
+                additional_flags: Some(vec!["--stderr-to-stdout"]),
+                ..ExecuteConfig::default()
+            },
+            &command,
+        )?;
+        if !output.status.success() {
+            return Err(Error::Sandbox(format!(
+                "The version command of {} failed: {}",
+                language.code,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let version = self.read_file(output_file)?;
+        Ok(version
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(str::to_string))
+    }
+
     /// Copy a file from outside the sandbox to inside the sandbox.
     /// The destination is relative to the 'box' folder in the sandbox.
-    pub fn copy_into(
-        &self,
-        source: &str,
-        destination: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn copy_into(&self, source: &str, destination: &str) -> Result<()> {
         let source_path = Path::new(source);
         let destination_path = self.path.join("box").join(destination);
 
-        std::fs::copy(&source_path, &destination_path)?;
+        // The destination is replaced instead of written through, as a program run in the
+        // sandbox may have replaced it with a link to a file outside of the sandbox.
+        remove_if_exists(&destination_path)?;
+        std::fs::copy(source_path, &destination_path)?;
         log::trace!(
             "Copied (into sandbox) {:?} to {:?}.",
             &source_path,
@@ -268,17 +591,63 @@ impl Sandbox {
         Ok(())
     }
 
+    /// Write a file inside the sandbox.
+    /// The destination is relative to the 'box' folder in the sandbox.
+    pub fn write_file(&self, destination: &str, content: &str) -> Result<()> {
+        let destination_path = self.path.join("box").join(destination);
+
+        std::fs::write(&destination_path, content)?;
+        log::trace!("Wrote (into sandbox) {:?}.", &destination_path);
+
+        Ok(())
+    }
+
+    /// Write an executable file inside the sandbox, replacing the existing file.
+    /// The destination is relative to the 'box' folder in the sandbox.
+    pub fn write_executable(&self, destination: &str, content: &[u8]) -> Result<()> {
+        let destination_path = self.path.join("box").join(destination);
+
+        remove_if_exists(&destination_path)?;
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o755)
+            .open(&destination_path)?
+            .write_all(content)?;
+        log::trace!("Wrote (into sandbox) {:?}.", &destination_path);
+
+        Ok(())
+    }
+
+    /// Get the path of a file inside the sandbox, e.g. for copying it out of the sandbox.
+    /// The source is relative to the 'box' folder in the sandbox.
+    pub fn file_path(&self, source: &str) -> PathBuf {
+        self.path.join("box").join(source)
+    }
+
     /// Copy a file from a sandbox to another or the same sandbox.
     pub fn copy_across_sandbox(
         &self,
         sb_destination: &Sandbox,
         source: &str,
         destination: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<()> {
         let source_path = self.path.join("box").join(source);
         let destination_path = sb_destination.path.join("box").join(destination);
 
-        std::fs::copy(&source_path, &destination_path)?;
+        if let Some(parent) = destination_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Neither the source nor the destination is followed if it is a link, as the program run
+        // in the sandbox may have created links to files outside of the sandbox.
+        let mut source_file = self.open_file(source)?;
+        remove_if_exists(&destination_path)?;
+        let mut destination_file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&destination_path)?;
+        std::io::copy(&mut source_file, &mut destination_file)?;
+        destination_file.set_permissions(source_file.metadata()?.permissions())?;
         log::trace!(
             "Copied (between sandbox) {:?} to {:?}.",
             &source_path,
@@ -288,12 +657,190 @@ impl Sandbox {
         Ok(())
     }
 
+    /// Remove all the files inside the sandbox. Links are removed instead of followed.
+    pub fn clear(&self) -> Result<()> {
+        for entry in std::fs::read_dir(self.path.join("box"))? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(entry.path())?;
+            } else {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        log::trace!("Sandbox {} cleared.", self.id);
+
+        Ok(())
+    }
+
+    /// List the files inside the sandbox with their modification times, e.g. for finding the files
+    /// produced by a compilation. The paths are relative to the 'box' folder in the sandbox.
+    pub fn list_files(&self) -> Result<BTreeMap<String, SystemTime>> {
+        let mut files = BTreeMap::new();
+        list_files(&self.path.join("box"), Path::new(""), &mut files)?;
+
+        Ok(files)
+    }
+
     /// Read a file inside the sandbox.
     /// The source is relative to the 'box' folder in the sandbox.
-    pub fn read_file(&self, source: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let source_path = self.path.join("box").join(source);
-        let file_content = std::fs::read_to_string(source_path.to_str().unwrap())?;
+    pub fn read_file(&self, source: &str) -> Result<String> {
+        let mut file_content = String::new();
+        self.open_file(source)?.read_to_string(&mut file_content)?;
 
         Ok(file_content)
     }
+
+    /// Get the size of a file inside the sandbox in bytes.
+    /// The source is relative to the 'box' folder in the sandbox.
+    pub fn file_size(&self, source: &str) -> Result<u64> {
+        let source_path = self.path.join("box").join(source);
+
+        // The size of a link is taken instead of the file linked to.
+        Ok(std::fs::symlink_metadata(source_path)?.len())
+    }
+
+    /// Read the first and the last `limit` bytes of a file inside the sandbox.
+    /// The source is relative to the 'box' folder in the sandbox.
+    pub fn read_excerpt(&self, source: &str, limit: u64) -> Result<Excerpt> {
+        Ok(Excerpt::from_file(self.open_file(source)?, limit)?)
+    }
+
+    /// Open a regular file inside the sandbox for reading. Links are not followed, as a program
+    /// run in the sandbox may have replaced the file with a link to a file outside of the sandbox.
+    /// The source is relative to the 'box' folder in the sandbox.
+    fn open_file(&self, source: &str) -> Result<File> {
+        let file = OpenOptions::new()
+            .read(true)
+            // A FIFO is opened without waiting for a writer, and then rejected as not regular.
+            .custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK)
+            .open(self.path.join("box").join(source))?;
+        if !file.metadata()?.is_file() {
+            return Err(Error::Sandbox(format!(
+                "The file {} in sandbox {} is not a regular file.",
+                source, self.id
+            )));
+        }
+
+        Ok(file)
+    }
+}
+
+/// Remove a file or a link if it exists. A link is removed instead of the file linked to.
+pub(crate) fn remove_if_exists(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+fn list_files(root: &Path, folder: &Path, files: &mut BTreeMap<String, SystemTime>) -> Result<()> {
+    for entry in std::fs::read_dir(root.join(folder))? {
+        let entry = entry?;
+        let path = folder.join(entry.file_name());
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            list_files(root, &path, files)?;
+        } else if metadata.is_file() {
+            files.insert(path.to_string_lossy().to_string(), metadata.modified()?);
+        }
+    }
+
+    Ok(())
+}
+
+/// The files which are new or modified in the listing `after` compared to the listing `before`.
+pub fn changed_files(
+    before: &BTreeMap<String, SystemTime>,
+    after: &BTreeMap<String, SystemTime>,
+) -> Vec<String> {
+    after
+        .iter()
+        .filter(|(path, modified)| before.get(*path) != Some(*modified))
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_lock() {
+        let lock = SandboxLock::acquire(9999).unwrap();
+        assert!(SandboxLock::acquire(9999).is_err());
+        drop(lock);
+        assert!(SandboxLock::acquire(9999).is_ok());
+    }
+
+    #[test]
+    fn test_read_link() {
+        let folder = std::env::temp_dir().join(format!("minijudge-link-{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("box")).unwrap();
+        std::fs::write(folder.join("secret.txt"), "secret").unwrap();
+        std::fs::write(folder.join("box/out.txt"), "output").unwrap();
+        std::os::unix::fs::symlink(folder.join("secret.txt"), folder.join("box/link.txt")).unwrap();
+        let sandbox = Sandbox {
+            path: folder.clone(),
+            id: 9999,
+        };
+
+        let output = sandbox.read_file("out.txt");
+        let link = sandbox.read_file("link.txt");
+        let link_excerpt = sandbox.read_excerpt("link.txt", 1024);
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(output.unwrap(), "output");
+        assert!(link.is_err());
+        assert!(link_excerpt.is_err());
+    }
+
+    #[test]
+    fn test_copy_to_link() {
+        let folder = std::env::temp_dir().join(format!("minijudge-copy-{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("box")).unwrap();
+        std::fs::write(folder.join("host.txt"), "host").unwrap();
+        std::fs::write(folder.join("box/out.txt"), "output").unwrap();
+        std::os::unix::fs::symlink(folder.join("host.txt"), folder.join("box/best.txt")).unwrap();
+        let sandbox = Sandbox {
+            path: folder.clone(),
+            id: 9999,
+        };
+
+        sandbox
+            .copy_across_sandbox(&sandbox, "out.txt", "best.txt")
+            .unwrap();
+        let host = std::fs::read_to_string(folder.join("host.txt")).unwrap();
+        let best = sandbox.read_file("best.txt").unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(host, "host");
+        assert_eq!(best, "output");
+    }
+
+    #[test]
+    fn test_changed_files() {
+        let folder = std::env::temp_dir().join(format!("minijudge-test-{}", std::process::id()));
+        std::fs::create_dir_all(folder.join("box")).unwrap();
+        std::fs::write(folder.join("box/Main.java"), "class Main {}").unwrap();
+        let before = {
+            let mut files = BTreeMap::new();
+            list_files(&folder.join("box"), Path::new(""), &mut files).unwrap();
+            files
+        };
+
+        std::fs::write(folder.join("box/Main.class"), "").unwrap();
+        std::fs::create_dir_all(folder.join("box/pkg")).unwrap();
+        std::fs::write(folder.join("box/pkg/Main$Reader.class"), "").unwrap();
+        let mut after = BTreeMap::new();
+        list_files(&folder.join("box"), Path::new(""), &mut after).unwrap();
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(
+            changed_files(&before, &after),
+            vec![
+                "Main.class".to_string(),
+                "pkg/Main$Reader.class".to_string()
+            ]
+        );
+    }
 }
@@ -1,11 +1,18 @@
-use crate::cli::{Opts, Metadata, Testcase};
-use judge_definitions::JudgeOutput;
+use crate::judge::JudgeOutput;
+use crate::languages::{Language, TemplateContext};
+use crate::observer::Observer;
+use crate::problem::{Problem, Testcase};
 use std::sync::{Arc, Mutex};
 
 pub struct AppState {
-    pub opts: Opts,
-    pub metadata: Metadata,
+    pub problem: Problem,
+    pub language: Language,
+    /// The values passed to the templates of the execute command.
+    pub context: TemplateContext,
+    /// The compiled checker, which is written into the sandbox before each check, so that a
+    /// submission cannot replace it.
+    pub checker: Arc<Vec<u8>>,
     pub judge_output: Arc<Mutex<JudgeOutput>>,
     pub testcases_stack: Arc<Mutex<Vec<Testcase>>>,
-    pub socket: Option<Arc<Mutex<zmq::Socket>>>,
+    pub observers: Vec<Arc<dyn Observer>>,
 }
@@ -0,0 +1,132 @@
+use crate::cli::Opts;
+use minijudge_rust::error::Result;
+use minijudge_rust::format::{render_junit_cases, JunitCase, JunitFailure, VerdictFormat};
+use minijudge_rust::judge::JudgeOutput;
+use minijudge_rust::languages::LanguageRegistry;
+use minijudge_rust::problem::{solution_path, Problem, Solution};
+/// This module contains the verification of a problem, checking that every reference solution
+/// listed in the metadata gets its expected verdict.
+use minijudge_rust::sandbox::SandboxPool;
+use minijudge_rust::{Judge, Submission};
+
+/// The result of judging a reference solution.
+pub struct SolutionResult {
+    pub solution: Solution,
+    pub judge_output: JudgeOutput,
+}
+
+impl SolutionResult {
+    pub fn matches(&self) -> bool {
+        self.judge_output.verdict == self.solution.expected
+    }
+}
+
+/// Judge all reference solutions listed in the metadata and print a matrix of their verdicts on
+/// each testcase. Returns whether all solutions get their expected verdicts.
+pub fn verify(
+    opts: &Opts,
+    problem: &Problem,
+    languages: LanguageRegistry,
+    sandboxes: &SandboxPool,
+) -> Result<bool> {
+    let judge = Judge::builder()
+        .problem(problem.clone())
+        .languages(languages)
+        .sandboxes(sandboxes)
+        .build()?;
+
+    let mut results: Vec<SolutionResult> = Vec::new();
+    for solution in problem.metadata.solutions.iter() {
+        log::info!("Judging solution {}.", solution.source);
+        let judge_output = judge.judge(&Submission {
+            id: None,
+            source: solution_path(opts.metadata_path(), solution),
+            language: solution.language.clone(),
+        })?;
+
+        results.push(SolutionResult {
+            solution: solution.clone(),
+            judge_output,
+        });
+    }
+
+    print_matrix(&results, problem.metadata.testcases.len());
+    write_reports(opts, &results)?;
+
+    Ok(results.iter().all(|r| r.matches()))
+}
+
+fn print_matrix(results: &[SolutionResult], testcase_count: usize) {
+    let source_width = results
+        .iter()
+        .map(|r| r.solution.source.len())
+        .fold("Solution".len(), usize::max);
+
+    let mut header = format!(
+        "{:<width$} {:<8} {:<8}",
+        "Solution",
+        "Expected",
+        "Verdict",
+        width = source_width
+    );
+    for i in 0..testcase_count {
+        header.push_str(&format!(" {:<4}", i + 1));
+    }
+    println!("{}", header);
+
+    for result in results {
+        let mut row = format!(
+            "{:<width$} {:<8} {:<8}",
+            result.solution.source,
+            result.solution.expected,
+            result.judge_output.verdict,
+            width = source_width
+        );
+        for testcase in result.judge_output.testcases.iter() {
+            row.push_str(&format!(" {:<4}", testcase.verdict));
+        }
+        if !result.matches() {
+            row.push_str(" MISMATCH");
+        }
+        println!("{}", row);
+    }
+}
+
+/// Write a JUnit report with a testcase for each solution to the files given in `--verdict`, so
+/// that the verification shows up in CI test reports. A solution not getting its expected verdict
+/// is reported as a failure.
+fn write_reports(opts: &Opts, results: &[SolutionResult]) -> Result<()> {
+    let cases: Vec<JunitCase> = results
+        .iter()
+        .map(|result| JunitCase {
+            name: result.solution.source.clone(),
+            time: result.judge_output.time,
+            failure: if result.matches() {
+                None
+            } else {
+                Some(JunitFailure {
+                    error: false,
+                    kind: result.judge_output.verdict.clone(),
+                    message: format!(
+                        "Expected {}, got {}.",
+                        result.solution.expected, result.judge_output.verdict
+                    ),
+                    detail: String::new(),
+                })
+            },
+        })
+        .collect();
+    let xml = render_junit_cases(&[("verify", cases)]);
+
+    for verdict_file in opts.verdict.iter() {
+        match opts.verdict_format_for(Some(verdict_file)) {
+            VerdictFormat::Junit => std::fs::write(verdict_file, &xml)?,
+            _ => log::warn!(
+                "Only JUnit reports are written when verifying. {} is skipped.",
+                verdict_file
+            ),
+        }
+    }
+
+    Ok(())
+}